@@ -0,0 +1,169 @@
+use std::os::unix::io::RawFd;
+#[cfg(any(feature = "x11", feature = "evdev", feature = "wayland-stub"))]
+use std::time::Instant;
+use traps::config;
+
+//Input backends turn the strokes of a display server or of input devices into events
+//Which backends exist is decided by the Cargo features x11, evdev and wayland-stub
+
+//Receives the events of the backend, together with the backend itself
+pub type Listener = Box<dyn FnMut(Event, &mut dyn InputBackend)>;
+
+pub trait InputBackend {
+    //Connects to the input devices and creates the overlay
+    fn init(&mut self);
+    //Reports Event::Readable, whenever the file descriptor becomes readable
+    fn watch(&mut self, fd: RawFd);
+    //Takes over the settings of a reloaded config, which can change while running
+    fn apply_config(&mut self, config: &config::Config);
+    fn event_loop(&mut self, listener: Listener);
+    //The event loop returns, once the listener is done with the current event
+    fn stop_event_loop(&mut self);
+    //The screen with the monitors at the last stroke sequence
+    fn screen(&self) -> traps::Screen;
+    //Strokes are drawn with a trigger button, without an overlay
+    fn is_passive(&self) -> bool;
+    fn is_visible(&self) -> bool;
+    fn set_visible(&mut self, visible: bool);
+    //Shows the name and shape of the matched gesture after the stroke sequence
+    fn show_feedback(&self, text: &str, shape: &[Vec<traps::Point>]);
+    //Presses the keys in order and releases them in reverse order, false if that is not possible
+    fn send_key_chord(&mut self, keysyms: &[u32]) -> bool;
+    fn click(&mut self, button: u8) -> bool;
+    //The current machine, to select the sections of a reloaded config
+    fn machine(&self) -> config::Machine;
+    //None without windows
+    fn window_inspector(&self) -> Option<Box<dyn WindowInspector>>;
+}
+
+//Looks up windows, also while the backend runs its event loop
+pub trait WindowInspector {
+    //The client window at the root coordinates
+    fn window_at(&self, x: i16, y: i16) -> traps::WindowInfo;
+}
+
+//Keys are only bound on an overlay
+#[cfg_attr(not(feature = "x11"), allow(dead_code))]
+#[derive(Debug)]
+pub enum Event {
+    //Coordinates and the time in milliseconds
+    Point(i16, i16, u32),
+    Start,
+    Stop,
+    //No further stroke followed within the stroke pause
+    Finish,
+    //The window that was focused, when the stroke sequence began
+    Focus(traps::WindowInfo),
+    //A watched file descriptor is readable
+    Readable(RawFd),
+    //A bound key was pressed on the overlay
+    Key(config::KeyAction),
+    //The strokes drawn so far were discarded through a key binding
+    Cancel,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    #[cfg(feature = "x11")]
+    X11,
+    #[cfg(feature = "evdev")]
+    Evdev,
+    #[cfg(feature = "wayland-stub")]
+    Wayland,
+}
+
+impl Kind {
+    //The compiled backends, the first one is the default
+    pub const ALL: &'static [Kind] = &[
+        #[cfg(feature = "x11")]
+        Kind::X11,
+        #[cfg(feature = "evdev")]
+        Kind::Evdev,
+        #[cfg(feature = "wayland-stub")]
+        Kind::Wayland,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            #[cfg(feature = "x11")]
+            Kind::X11 => "x11",
+            #[cfg(feature = "evdev")]
+            Kind::Evdev => "evdev",
+            #[cfg(feature = "wayland-stub")]
+            Kind::Wayland => "wayland",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Kind> {
+        Self::ALL.iter().copied().find(|kind| kind.name() == name)
+    }
+
+    //The current machine, with the resolution if the backend knows it
+    pub fn machine(self) -> config::Machine {
+        match self {
+            #[cfg(feature = "x11")]
+            Kind::X11 => super::ui::machine(),
+            #[allow(unreachable_patterns)]
+            _ => config::Machine::current(),
+        }
+    }
+
+    pub fn create(
+        self,
+        #[cfg_attr(
+            not(any(feature = "x11", feature = "evdev", feature = "wayland-stub")),
+            allow(unused_variables)
+        )]
+        config: &config::Config,
+    ) -> Result<Box<dyn InputBackend>, String> {
+        match self {
+            #[cfg(feature = "x11")]
+            Kind::X11 => Ok(Box::new(super::ui::UI::new(config)?)),
+            #[cfg(feature = "evdev")]
+            Kind::Evdev => Ok(Box::new(super::evdev::EvdevBackend::new(config)?)),
+            #[cfg(feature = "wayland-stub")]
+            Kind::Wayland => Ok(Box::new(super::wayland::WaylandBackend::new(config))),
+        }
+    }
+}
+
+//Waits until one of the file descriptors is readable or the deadline has passed
+//Returns the readable file descriptors, including closed ones, so reading them shows the error
+#[cfg(any(feature = "x11", feature = "evdev", feature = "wayland-stub"))]
+pub fn wait(fds: &[RawFd], deadline: Option<Instant>) -> Vec<RawFd> {
+    let timeout = deadline.map_or(-1, |deadline| {
+        deadline
+            .saturating_duration_since(Instant::now())
+            .as_millis() as i32
+            + 1
+    });
+    let mut pollfds: Vec<libc::pollfd> = fds
+        .iter()
+        .map(|fd| libc::pollfd {
+            fd: *fd,
+            events: libc::POLLIN,
+            revents: 0,
+        })
+        .collect();
+    unsafe {
+        libc::poll(pollfds.as_mut_ptr(), pollfds.len() as libc::nfds_t, timeout);
+    }
+    pollfds
+        .iter()
+        .filter(|pollfd| pollfd.revents & (libc::POLLIN | libc::POLLHUP | libc::POLLERR) != 0)
+        .map(|pollfd| pollfd.fd)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kinds() {
+        for kind in Kind::ALL {
+            assert_eq!(Kind::from_name(kind.name()), Some(*kind));
+        }
+        assert_eq!(Kind::from_name("none"), None);
+    }
+}
@@ -1,144 +1,1292 @@
-use super::lib::{Gesture, GestureAttributes, GestureRecorder, Point};
-use super::ui;
+use crate::{
+    keysym, Action, Area, Builtin, Constraint, Coordinate, Edge, Gesture, GestureAttributes,
+    Matcher, Point, Preprocessing, ScreenPoint,
+};
+use crate::toml_config::TomlConfig;
+use regex::Regex;
+use std::f32::consts::PI;
+use std::fmt;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-fn parse_hex(hex_code: &str) -> u32 {
-    let r: u8 = u8::from_str_radix(&hex_code[1..3], 16).unwrap();
-    let g: u8 = u8::from_str_radix(&hex_code[3..5], 16).unwrap();
-    let b: u8 = u8::from_str_radix(&hex_code[5..7], 16).unwrap();
-    let a: u8 = u8::from_str_radix(&hex_code[7..9], 16).unwrap();
+//Maximum distance between two sampled points of a primitive
+const SAMPLE_DISTANCE: f32 = 20.0;
+//Distance in pixels from a screen edge, which still counts as on the edge
+const DEFAULT_EDGE_MARGIN: i16 = 5;
+//Keys, which belong to the gesture defined by the next points line
+pub const GESTURE_KEYS: &[&str] = &[
+    "name", "is_relative", "action", "tolerance", "matcher", "min_score", "start_area", "stop_area",
+    "min_duration", "max_duration", "min_length", "max_deviation", "start_edge", "monitor",
+    "window_class", "window_name", "preprocess",
+];
+pub const CONFIG_FILE_NAME: &str = "trapsrc";
+//Preferred over trapsrc, when it exists
+pub const TOML_CONFIG_FILE_NAME: &str = "traps.toml";
+//Fragments in the config directory, loaded after the main config
+const CONF_D_PATTERN: &str = "conf.d/*.conf";
 
-    ui::color_to_argb(r as u32, g as u32, b as u32, a as u32)
+//#rrggbbaa
+pub fn parse_hex(hex_code: &str) -> Option<u32> {
+    if hex_code.len() != 9 || !hex_code.starts_with('#') || !hex_code.is_ascii() {
+        return None;
+    }
+    let r: u8 = u8::from_str_radix(&hex_code[1..3], 16).ok()?;
+    let g: u8 = u8::from_str_radix(&hex_code[3..5], 16).ok()?;
+    let b: u8 = u8::from_str_radix(&hex_code[5..7], 16).ok()?;
+    let a: u8 = u8::from_str_radix(&hex_code[7..9], 16).ok()?;
+
+    Some(color_to_argb(r as u32, g as u32, b as u32, a as u32))
+}
+
+//Premultiplied ARGB, as used for the colors of the overlay
+pub fn color_to_argb(r: u32, g: u32, b: u32, a: u32) -> u32 {
+    (a << 24) | ((r << 16) * a / 255) | ((g << 8) * a / 255) | (b * a / 255)
+}
+
+//Expects a value, that can be parsed as T, and describes it otherwise
+fn parse_value<T: FromStr>(value: &str, description: &str) -> Result<T, String> {
+    value.parse().map_err(|_| expected(description, value))
+}
+
+pub fn expected(description: &str, value: &str) -> String {
+    format!("expected {}, found '{}'", description, value)
+}
+
+//1-based column of the byte offset
+fn column(line: &str, offset: usize) -> usize {
+    line[..offset.min(line.len())].chars().count() + 1
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+//A problem in the config, lines and columns start at 1 and are 0 for the whole file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub file: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn error(file: &Path, line: usize, column: usize, message: String) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            file: file.to_owned(),
+            line,
+            column,
+            message,
+        }
+    }
+
+    pub fn warning(file: &Path, line: usize, column: usize, message: String) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Self::error(file, line, column, message)
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        if self.line == 0 {
+            write!(f, "{}: {}: {}", self.file.display(), severity, self.message)
+        } else {
+            write!(
+                f,
+                "{}:{}:{}: {}: {}",
+                self.file.display(),
+                self.line,
+                self.column,
+                severity,
+                self.message
+            )
+        }
+    }
+}
+
+//What a key binding on the overlay does
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyAction {
+    //Discards the strokes drawn so far and hides the overlay
+    Cancel,
+    //Runs the actions of the gesture with this name
+    Gesture(String),
+    Action(Action),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyBinding {
+    pub keysym: u32,
+    //Modifier mask, like the one of the trigger button
+    pub modifiers: u16,
+    pub action: KeyAction,
 }
 
 pub struct Config {
     pub fg: u32,
     pub bg: u32,
     pub r: u32,
+    pub min_score: f32,
+    //Applied to strokes before they are matched against gestures without their own
+    pub preprocessing: Preprocessing,
+    pub stroke_pause: u64,
+    //Delta gestures with one stroke run as soon as their last point is reached, before the stroke ends
+    pub fast_trigger: bool,
+    //The overlay covers all monitors or only the one under the pointer
+    pub overlay_monitor: OverlayMonitor,
+    //start_area and stop_area are relative to the monitor of the point
+    pub monitor_areas: bool,
+    //Draw the trail anti-aliased through XRender
+    pub trail_antialias: bool,
+    //Milliseconds, after which points of the trail have faded out, 0 keeps the whole trail
+    pub trail_fade: u64,
+    //Milliseconds, for which the matched gesture is shown on the overlay, 0 disables the feedback
+    pub feedback_time: u64,
+    //X core font of the feedback text
+    pub feedback_font: String,
+    pub feedback_color: u32,
+    //Also draw the points of the matched gesture over the stroke
+    pub feedback_shape: bool,
+    //Button and modifier mask grabbed on the root window, instead of showing the overlay
+    pub trigger: Option<(u8, u16)>,
+    //Reload the config, whenever the file changes
+    pub watch: bool,
+    pub key_bindings: Vec<KeyBinding>,
     pub gestures: Vec<Gesture>,
+    //Loaded files and glob patterns of included files, which are watched for changes
+    pub files: Vec<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            fg: color_to_argb(255, 255, 255, 255),
+            bg: color_to_argb(0, 0, 0, 150),
+            r: 10,
+            min_score: 0.0,
+            preprocessing: Preprocessing::default(),
+            stroke_pause: 0,
+            fast_trigger: false,
+            overlay_monitor: OverlayMonitor::All,
+            monitor_areas: false,
+            trail_antialias: false,
+            trail_fade: 0,
+            feedback_time: 0,
+            feedback_font: "fixed".to_owned(),
+            feedback_color: color_to_argb(255, 165, 0, 255),
+            feedback_shape: false,
+            trigger: None,
+            watch: false,
+            key_bindings: vec![
+                KeyBinding {
+                    keysym: keysym::from_name("escape").unwrap(),
+                    modifiers: 0,
+                    action: KeyAction::Cancel,
+                },
+                KeyBinding {
+                    keysym: keysym::from_name("tab").unwrap(),
+                    modifiers: 0,
+                    action: KeyAction::Action(Action::Builtin(Builtin::Hide)),
+                },
+            ],
+            gestures: Vec::new(),
+            files: Vec::new(),
+        }
+    }
+}
+
+//Where the overlay is shown
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OverlayMonitor {
+    All,
+    //The monitor under the pointer, when the overlay is shown
+    Pointer,
+}
+
+impl OverlayMonitor {
+    pub fn from_name(name: &str) -> Option<OverlayMonitor> {
+        match name {
+            "all" => Some(OverlayMonitor::All),
+            "pointer" => Some(OverlayMonitor::Pointer),
+            _ => None,
+        }
+    }
+}
+
+//Facts about the current machine, which [host = ...] and [resolution = ...] sections are matched against
+#[derive(Debug, Clone, Default)]
+pub struct Machine {
+    pub hostname: String,
+    pub resolution: Option<(u16, u16)>,
+}
+
+impl Machine {
+    //The resolution is unknown here, the frontend fills it in from its screen
+    pub fn current() -> Machine {
+        let mut buffer = [0u8; 256];
+        let hostname = match unsafe { libc::gethostname(buffer.as_mut_ptr() as *mut libc::c_char, buffer.len()) } {
+            0 => String::from_utf8_lossy(buffer.split(|byte| *byte == 0).next().unwrap_or_default()).into_owned(),
+            _ => String::new(),
+        };
+        Machine { hostname, resolution: None }
+    }
+
+    //host = name or resolution = WIDTHxHEIGHT, None for other sections
+    pub fn matches(&self, key: &str, value: &str) -> Option<Result<bool, String>> {
+        match key {
+            "host" => Some(Ok(self.hostname == value)),
+            "resolution" => Some(
+                Config::parse_resolution(value)
+                    .map(|resolution| self.resolution == Some(resolution))
+                    .ok_or_else(|| expected("a resolution like 1920x1080", value)),
+            ),
+            _ => None,
+        }
+    }
+}
+
+//Collects the config from the main file with its includes, followed by the conf.d fragments
+//Settings, and gestures with the same name and window scope, which are loaded later, override earlier ones
+pub struct Loader {
+    pub config: Config,
+    pub diagnostics: Vec<Diagnostic>,
+    pub machine: Machine,
+    //Files, which are currently loaded, to detect include cycles
+    files: Vec<PathBuf>,
+    //File, line and column of every gesture, for checks once everything is loaded
+    origins: Vec<(PathBuf, usize, usize)>,
+}
+
+impl Loader {
+    pub fn new(machine: Machine) -> Loader {
+        Loader {
+            config: Config::default(),
+            diagnostics: Vec::new(),
+            machine,
+            files: Vec::new(),
+            origins: Vec::new(),
+        }
+    }
+
+    //A missing optional file counts as empty
+    pub fn load_file(&mut self, path: &Path, optional: bool) {
+        self.watch(path);
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_owned());
+        if self.files.contains(&canonical) {
+            self.diagnostics.push(Diagnostic::error(path, 0, 0, "the file includes itself".to_owned()));
+            return;
+        }
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(error) if optional && error.kind() == ErrorKind::NotFound => return,
+            Err(error) => {
+                self.diagnostics
+                    .push(Diagnostic::error(path, 0, 0, format!("could not read the file: {}", error)));
+                return;
+            }
+        };
+        self.files.push(canonical);
+        match path.extension() {
+            Some(extension) if extension == "toml" => TomlConfig::parse_into(self, &content, path),
+            _ => Config::parse_into(self, &content, path),
+        }
+        self.files.pop();
+    }
+
+    //Paths are relative to the including file and may start with ~/ or contain globs
+    pub fn include(&mut self, pattern: &str, file: &Path) -> Result<(), String> {
+        let path = match pattern.strip_prefix("~/") {
+            Some(rest) => dirs::home_dir().ok_or("the home directory is unknown")?.join(rest),
+            None => file.parent().unwrap_or_else(|| Path::new("")).join(pattern),
+        };
+        self.load_pattern(&path)
+    }
+
+    //Matching files are loaded in alphabetical order, a path without globs has to exist
+    pub fn load_pattern(&mut self, path: &Path) -> Result<(), String> {
+        let text = path.to_str().ok_or_else(|| format!("invalid path '{}'", path.display()))?;
+        if glob::Pattern::escape(text) == text {
+            if !path.exists() {
+                return Err(format!("'{}' does not exist", text));
+            }
+            self.load_file(path, false);
+            return Ok(());
+        }
+        let paths = glob::glob(text).map_err(|error| format!("invalid pattern '{}': {}", text, error.msg))?;
+        //New matching files change the config as well
+        self.watch(path);
+        for path in paths.flatten() {
+            self.load_file(&path, false);
+        }
+        Ok(())
+    }
+
+    fn watch(&mut self, path: &Path) {
+        if !self.config.files.iter().any(|file| file == path) {
+            self.config.files.push(path.to_owned());
+        }
+    }
+
+    pub fn add_gesture(&mut self, gesture: Gesture, file: &Path, line: usize, column: usize) {
+        let origin = (file.to_owned(), line, column);
+        let existing = self
+            .config
+            .gestures
+            .iter()
+            .position(|existing| {
+                !gesture.name.is_empty()
+                    && existing.name == gesture.name
+                    && existing.window_scope() == gesture.window_scope()
+            });
+        match existing {
+            Some(index) => {
+                self.config.gestures[index] = gesture;
+                self.origins[index] = origin;
+            }
+            None => {
+                self.config.gestures.push(gesture);
+                self.origins.push(origin);
+            }
+        }
+    }
+
+    //Fails if any error was found, warnings are returned either way
+    pub fn finish(mut self) -> Result<(Config, Vec<Diagnostic>), Vec<Diagnostic>> {
+        //Without a pause, every stroke ends the sequence, so later strokes are never recorded
+        if self.config.stroke_pause == 0 {
+            for (gesture, (file, line, column)) in self.config.gestures.iter().zip(&self.origins) {
+                if gesture.stroke_count() > 1 {
+                    self.diagnostics.push(Diagnostic::warning(
+                        file,
+                        *line,
+                        *column,
+                        format!("the gesture '{}' has several strokes, which need a stroke_pause above 0", gesture.name),
+                    ));
+                }
+            }
+        }
+        if self.diagnostics.iter().any(|diagnostic| diagnostic.severity == Severity::Error) {
+            return Err(self.diagnostics);
+        }
+        Ok((self.config, self.diagnostics))
+    }
 }
 
 impl Config {
-    pub fn new() -> Config {
-        let content = Config::config_file_content();
+    pub fn load(machine: Machine) -> Result<(Config, Vec<Diagnostic>), Vec<Diagnostic>> {
+        let mut loader = Loader::new(machine);
+        loader.load_file(&Self::config_path(), true);
+        let fragments = Self::config_dir().join(CONF_D_PATTERN);
+        if let Err(message) = loader.load_pattern(&fragments) {
+            loader.diagnostics.push(Diagnostic::error(&fragments, 0, 0, message));
+        }
+        loader.finish()
+    }
 
-        let mut fg = ui::color_to_argb(255, 255, 255, 255);
-        let mut bg = ui::color_to_argb(0, 0, 0, 150);
-        let mut r = 10;
-        let mut gestures = Vec::new();
+    pub fn load_from(path: &Path, machine: Machine) -> Result<(Config, Vec<Diagnostic>), Vec<Diagnostic>> {
+        let mut loader = Loader::new(machine);
+        loader.load_file(path, true);
+        loader.finish()
+    }
 
+    pub fn parse(content: &str, file: &Path) -> Result<(Config, Vec<Diagnostic>), Vec<Diagnostic>> {
+        let mut loader = Loader::new(Machine::default());
+        Self::parse_into(&mut loader, content, file);
+        loader.finish()
+    }
+
+    fn parse_into(loader: &mut Loader, content: &str, file: &Path) {
+        //Settings and gestures of sections for other machines are checked, but end up here
+        let mut skipped = Config::default();
+        let mut active = true;
         let mut attributes = GestureAttributes::default();
+        //Line of the first attribute of a gesture, which has no points yet
+        let mut attributes_line = None;
+        //Set by a [window_class = ...] or [window_name = ...] line for all following gestures
+        let mut scope: Option<Constraint> = None;
+        //[host = ...] and [resolution = ...] keep the window scope and the other way around, [] resets both
 
-        let relevant_lines = content.lines().filter(|line| !line.starts_with('#'));
-        for line in relevant_lines {
-            let vec: Vec<&str> = line.splitn(2, '=').collect();
-            if vec.len() == 2 {
-                let key = vec.get(0).unwrap().trim();
-                let value = vec.get(1).unwrap().trim();
+        for (index, line) in content.lines().enumerate() {
+            let line_number = index + 1;
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            let indent = line.len() - line.trim_start().len();
+            if trimmed.starts_with('[') && trimmed.ends_with(']') {
+                let inside = &trimmed[1..trimmed.len() - 1];
+                let condition = inside
+                    .split_once('=')
+                    .and_then(|(key, value)| loader.machine.matches(key.trim(), value.trim()));
+                let result = match condition {
+                    Some(matches) => matches.map(|matches| active = matches),
+                    None => Self::parse_scope(inside).map(|constraint| {
+                        if inside.trim().is_empty() {
+                            active = true;
+                        }
+                        scope = constraint;
+                    }),
+                };
+                if let Err(message) = result {
+                    loader.diagnostics.push(Diagnostic::error(
+                        file,
+                        line_number,
+                        column(line, indent),
+                        message,
+                    ));
+                }
+                continue;
+            }
+            let (key, raw_value) = match line.split_once('=') {
+                Some(parts) => parts,
+                None => {
+                    loader.diagnostics.push(Diagnostic::error(
+                        file,
+                        line_number,
+                        column(line, indent),
+                        "expected key = value".to_owned(),
+                    ));
+                    continue;
+                }
+            };
+            let key = key.trim();
+            let value = raw_value.trim();
+            let value_offset = line.len() - raw_value.trim_start().len();
+            if GESTURE_KEYS.contains(&key) {
+                attributes_line.get_or_insert(line_number);
+            }
 
-                match key {
-                    "name" => {
-                        attributes.name = value;
-                    }
-                    "is_relative" => {
-                        attributes.is_relative = Self::parse_is_relative(value);
+            if key == "include" {
+                if active {
+                    if let Err(message) = loader.include(value, file) {
+                        loader.diagnostics.push(Diagnostic::error(file, line_number, column(line, value_offset), message));
                     }
-                    "action" => {
-                        attributes.action = value;
-                    }
-                    "tolerance" => {
-                        attributes.tolerance = Self::parse_tolerance(value);
-                    }
-                    "points" => {
-                        let points = Self::parse_points(value);
-                        let mut gesture = Gesture::new(&attributes);
-                        gesture.add_points(points);
-                        gestures.push(gesture);
-                        attributes = GestureAttributes::default();
-                    }
-                    "bg" => {
-                        bg = parse_hex(value);
-                    }
-                    "fg" => {
-                        fg = parse_hex(value);
-                    }
-                    "r" => {
-                        r = value.parse().expect("Expected int in config");
+                }
+                continue;
+            }
+
+            let config = if active { &mut loader.config } else { &mut skipped };
+            let mut new_gesture = None;
+            let mut origin = (0, 0);
+            let result = match key {
+                "name" => {
+                    attributes.name = value;
+                    Ok(())
+                }
+                "is_relative" => {
+                    parse_value(value, "true or false").map(|is_relative| attributes.is_relative = is_relative)
+                }
+                //Repeated action lines are chained and run in order
+                "action" => Action::parse(value).map(|action| attributes.actions.push(action)),
+                "tolerance" => {
+                    parse_value(value, "a tolerance in pixels").map(|tolerance| attributes.tolerance = tolerance)
+                }
+                "matcher" => Matcher::from_name(value)
+                    .map(|matcher| attributes.matcher = matcher)
+                    .ok_or_else(|| expected("delta, template or rotating_template", value)),
+                "min_score" => {
+                    parse_value(value, "a score").map(|min_score| attributes.min_score = Some(min_score))
+                }
+                "preprocess" => {
+                    Preprocessing::parse(value).map(|preprocessing| attributes.preprocessing = Some(preprocessing))
+                }
+                "start_area" | "stop_area" => Self::parse_area(value).map(|area| {
+                    attributes.constraints.push(match key {
+                        "start_area" => Constraint::StartArea(area),
+                        _ => Constraint::StopArea(area),
+                    })
+                }),
+                "min_duration" => parse_value(value, "milliseconds")
+                    .map(|min| attributes.constraints.push(Constraint::MinDuration(min))),
+                "max_duration" => parse_value(value, "milliseconds")
+                    .map(|max| attributes.constraints.push(Constraint::MaxDuration(max))),
+                "min_length" => parse_value(value, "a length in pixels")
+                    .map(|min| attributes.constraints.push(Constraint::MinLength(min))),
+                "max_deviation" => parse_value(value, "a distance in pixels")
+                    .map(|max| attributes.constraints.push(Constraint::MaxDeviation(max))),
+                "start_edge" => Self::parse_edge(value)
+                    .ok_or_else(|| expected("an edge or corner like top or top_left, and a margin", value))
+                    .map(|(edge, margin)| attributes.constraints.push(Constraint::StartEdge(edge, margin))),
+                "monitor" => {
+                    attributes.constraints.push(Constraint::Monitor(value.to_owned()));
+                    Ok(())
+                }
+                "window_class" | "window_name" => Self::parse_window_constraint(key, value)
+                    .map(|constraint| attributes.constraints.push(constraint)),
+                "points" => {
+                    match Self::parse_strokes(value) {
+                        Ok(strokes) => {
+                            if attributes.name.is_empty() {
+                                loader.diagnostics.push(Diagnostic::warning(
+                                    file,
+                                    line_number,
+                                    column(line, indent),
+                                    "the gesture has no name".to_owned(),
+                                ));
+                            }
+                            let mut gesture = Gesture::new(&attributes);
+                            origin = (line_number, column(line, indent));
+                            if let Some(constraint) = &scope {
+                                gesture.add_constraint(constraint.clone());
+                            }
+                            for stroke in strokes {
+                                gesture.add_stroke(stroke);
+                            }
+                            new_gesture = Some(gesture);
+                        }
+                        Err((offset, message)) => loader.diagnostics.push(Diagnostic::error(
+                            file,
+                            line_number,
+                            column(line, value_offset + offset),
+                            message,
+                        )),
                     }
-                    _ => {}
+                    attributes = GestureAttributes::default();
+                    attributes_line = None;
+                    Ok(())
                 }
+                "bg" => parse_hex(value)
+                    .map(|color| config.bg = color)
+                    .ok_or_else(|| expected("a color like #rrggbbaa", value)),
+                "fg" => parse_hex(value)
+                    .map(|color| config.fg = color)
+                    .ok_or_else(|| expected("a color like #rrggbbaa", value)),
+                "r" => parse_value(value, "a radius in pixels").map(|radius| config.r = radius),
+                "global_min_score" => parse_value(value, "a score").map(|score| config.min_score = score),
+                "global_preprocess" => Preprocessing::parse(value).map(|preprocessing| config.preprocessing = preprocessing),
+                "stroke_pause" => parse_value(value, "milliseconds").map(|pause| config.stroke_pause = pause),
+                "fast_trigger" => parse_value(value, "true or false").map(|enabled| config.fast_trigger = enabled),
+                "overlay_monitor" => OverlayMonitor::from_name(value)
+                    .map(|monitor| config.overlay_monitor = monitor)
+                    .ok_or_else(|| expected("all or pointer", value)),
+                "monitor_areas" => parse_value(value, "true or false").map(|enabled| config.monitor_areas = enabled),
+                "trail_antialias" => parse_value(value, "true or false").map(|enabled| config.trail_antialias = enabled),
+                "trail_fade" => parse_value(value, "milliseconds").map(|time| config.trail_fade = time),
+                "feedback_time" => parse_value(value, "milliseconds").map(|time| config.feedback_time = time),
+                "feedback_font" => {
+                    config.feedback_font = value.to_owned();
+                    Ok(())
+                }
+                "feedback_color" => parse_hex(value)
+                    .map(|color| config.feedback_color = color)
+                    .ok_or_else(|| expected("a color like #rrggbbaa", value)),
+                "feedback_shape" => parse_value(value, "true or false").map(|enabled| config.feedback_shape = enabled),
+                "watch_config" => parse_value(value, "true or false").map(|enabled| config.watch = enabled),
+                "trigger_button" => Self::parse_trigger(value)
+                    .map(|button| config.trigger = Some(button))
+                    .ok_or_else(|| expected("modifiers and a button like super+3", value)),
+                _ if key.starts_with("key.") => Self::parse_key_binding(&key[4..], value)
+                    .map(|(keysym, modifiers, action)| config.bind(keysym, modifiers, action)),
+                _ => {
+                    loader.diagnostics.push(Diagnostic::warning(
+                        file,
+                        line_number,
+                        column(line, indent),
+                        format!("unknown key '{}'", key),
+                    ));
+                    Ok(())
+                }
+            };
+            if let Err(message) = result {
+                loader.diagnostics.push(Diagnostic::error(file, line_number, column(line, value_offset), message));
+            }
+            if let Some(gesture) = new_gesture.filter(|_| active) {
+                loader.add_gesture(gesture, file, origin.0, origin.1);
             }
         }
+        if let Some(line_number) = attributes_line {
+            loader.diagnostics.push(Diagnostic::warning(
+                file,
+                line_number,
+                1,
+                "the gesture has no points and is ignored".to_owned(),
+            ));
+        }
+    }
 
-        Config {
-            fg,
-            bg,
-            r,
-            gestures,
+    //WIDTHxHEIGHT
+    fn parse_resolution(value: &str) -> Option<(u16, u16)>{
+        let (width, height) = value.split_once('x')?;
+        Some((width.trim().parse().ok()?, height.trim().parse().ok()?))
+    }
+
+    //[window_class = ...], [window_name = ...] or [] for all windows
+    fn parse_scope(inside: &str) -> Result<Option<Constraint>, String>{
+        if inside.trim().is_empty() {
+            return Ok(None);
+        }
+        match inside.split_once('=') {
+            Some((key, value)) => Self::parse_window_constraint(key.trim(), value.trim()).map(Some),
+            None => Err(expected("window_class = ..., window_name = ... or nothing", inside)),
         }
     }
 
-    fn parse_is_relative(value: &str) -> bool{
-        match value.parse(){
-            Err(_) => false,
-            Ok(value) => value
+    //Modifiers and one key joined by +, e.g. ctrl+r
+    //The action is cancel, a builtin, gesture: followed by a gesture name or an action, none removes the binding
+    pub fn parse_key_binding(chord: &str, value: &str) -> Result<(u32, u16, Option<KeyAction>), String>{
+        let keysyms = keysym::parse_chord(chord).ok_or_else(|| format!("unknown key in '{}'", chord))?;
+        let mut modifiers = 0;
+        let mut keys = Vec::new();
+        for keysym in keysyms{
+            match keysym{
+                keysym::SHIFT => modifiers |= keysym::SHIFT_MASK,
+                keysym::CONTROL => modifiers |= keysym::CONTROL_MASK,
+                keysym::ALT => modifiers |= keysym::ALT_MASK,
+                keysym::SUPER => modifiers |= keysym::SUPER_MASK,
+                keysym => keys.push(keysym),
+            }
+        }
+        if keys.len() != 1{
+            return Err(expected("modifiers and one key like ctrl+r", chord));
         }
+        let action = match value{
+            "none" => None,
+            "cancel" => Some(KeyAction::Cancel),
+            _ => Some(match (Builtin::from_name(value), value.strip_prefix("gesture:")){
+                (Some(builtin), _) => KeyAction::Action(Action::Builtin(builtin)),
+                (None, Some(name)) => KeyAction::Gesture(name.trim().to_owned()),
+                (None, None) => KeyAction::Action(Action::parse(value)?),
+            }),
+        };
+        Ok((keys[0], modifiers, action))
     }
 
-    fn parse_tolerance(value: &str) -> f32{
-        match value.parse(){
-            Err(_) => GestureRecorder::DEFAULT_TOLERANCE,
-            Ok(value) => value
+    //Replaces an earlier binding of the same keys
+    pub fn bind(&mut self, keysym: u32, modifiers: u16, action: Option<KeyAction>){
+        self.key_bindings.retain(|binding| (binding.keysym, binding.modifiers) != (keysym, modifiers));
+        if let Some(action) = action{
+            self.key_bindings.push(KeyBinding { keysym, modifiers, action });
         }
     }
 
-    fn parse_single_coordinate(text: &str) -> i16{
-        match text.parse(){
-            Err(_) => 0,
-            Ok(value) => value
+    //Modifiers and a button joined by +, e.g. super+3
+    pub fn parse_trigger(value: &str) -> Option<(u8, u16)>{
+        let mut parts: Vec<&str> = value.split('+').map(str::trim).collect();
+        let button = parts.pop()?.parse().ok().filter(|button| *button > 0)?;
+        let mut modifiers = 0;
+        for part in parts {
+            modifiers |= match part.to_lowercase().as_str() {
+                "shift" => keysym::SHIFT_MASK,
+                "ctrl" | "control" => keysym::CONTROL_MASK,
+                "alt" => keysym::ALT_MASK,
+                "super" | "win" => keysym::SUPER_MASK,
+                "any" => keysym::ANY_MODIFIER,
+                _ => return None,
+            };
         }
+        Some((button, modifiers))
     }
 
-    fn parse_default_point(brackets: &str) -> Option<(i16, i16)> {
-        let inside = brackets[1..brackets.len() - 1].trim();
-            let xy: Vec<&str> = inside.split(' ').collect();
-            if xy.len() == 2 {
-                return Some((
-                    Self::parse_single_coordinate(xy.get(0).unwrap()),
-                    Self::parse_single_coordinate(xy.get(1).unwrap()),
-                ));
+    pub fn parse_window_constraint(key: &str, value: &str) -> Result<Constraint, String>{
+        match key {
+            "window_class" => Ok(Constraint::WindowClass(value.to_owned())),
+            "window_name" => Regex::new(value)
+                .map(Constraint::WindowName)
+                .map_err(|_| format!("invalid regular expression '{}'", value)),
+            _ => Err(expected("window_class or window_name", key)),
+        }
+    }
+
+    //An edge or corner name with an optional margin in pixels
+    pub fn parse_edge(value: &str) -> Option<(Edge, i16)>{
+        let mut parts = value.split_whitespace();
+        let edge = Edge::from_name(parts.next()?)?;
+        match parts.next() {
+            Some(margin) => margin.parse().ok().map(|margin| (edge, margin)),
+            None => Some((edge, DEFAULT_EDGE_MARGIN)),
+        }
+    }
+
+    //between (x y) (x y), smaller (x y) or bigger (x y)
+    pub fn parse_area(value: &str) -> Result<Area, String>{
+        let description = "between (x y) (x y), smaller (x y) or bigger (x y)";
+        let (kind, rest) = value.split_once(' ').ok_or_else(|| expected(description, value))?;
+        let points = Self::parse_screen_points(rest)?;
+        match (kind, &points[..]) {
+            ("between", [start, end]) => Ok(Area::Between(start.clone(), end.clone())),
+            ("smaller", [max]) => Ok(Area::Smaller(max.clone())),
+            ("bigger", [min]) => Ok(Area::Bigger(min.clone())),
+            _ => Err(expected(description, value)),
+        }
+    }
+
+    fn parse_screen_points(text: &str) -> Result<Vec<ScreenPoint>, String>{
+        let mut points = Vec::new();
+        let mut rest = text.trim();
+        while !rest.is_empty() {
+            let (inside, after) = rest
+                .strip_prefix('(')
+                .and_then(|rest| rest.split_once(')'))
+                .ok_or_else(|| expected("a point like (x y)", rest))?;
+            match inside.split_whitespace().collect::<Vec<&str>>()[..] {
+                [x, y] => points.push(ScreenPoint::new(Self::parse_coordinate(x)?, Self::parse_coordinate(y)?)),
+                _ => return Err(expected("a point like (x y)", &rest[..inside.len() + 2])),
             }
-        None
+            rest = after.trim_start();
+        }
+        Ok(points)
+    }
+
+    //Pixels or a percentage of the screen size
+    fn parse_coordinate(text: &str) -> Result<Coordinate, String>{
+        let coordinate = match text.strip_suffix('%') {
+            Some(percentage) => percentage
+                .parse::<f32>()
+                .ok()
+                .map(|percentage| Coordinate::Fraction(percentage / 100.0)),
+            None => text.parse().ok().map(Coordinate::Pixels),
+        };
+        coordinate.ok_or_else(|| expected("pixels or a percentage", text))
+    }
+
+    fn parse_single_coordinate(text: &str) -> Result<i16, String>{
+        parse_value(text, "a coordinate in pixels")
     }
 
-    fn parse_point(points: &mut Vec<Point>, brackets: &str){
+    fn parse_default_point(brackets: &str) -> Result<(i16, i16), String> {
+        let inside = brackets[1..brackets.len() - 1].trim();
+        match inside.split_whitespace().collect::<Vec<&str>>()[..] {
+            [x, y] => Ok((Self::parse_single_coordinate(x)?, Self::parse_single_coordinate(y)?)),
+            _ => Err(expected("a point like (x y)", brackets)),
+        }
+    }
+
+    fn parse_point(points: &mut Vec<Point>, brackets: &str) -> Result<(), String>{
         if brackets.starts_with('(') && brackets.ends_with(')') {
-            if let Some((x, y)) = Self::parse_default_point(brackets){
-                points.push(Point::new(x,y));
-            }
+            let (x, y) = Self::parse_default_point(brackets)?;
+            points.push(Point::new(x,y));
+            Ok(())
         }
         else if brackets.starts_with("Circle(") && brackets.ends_with(')'){
+            Self::parse_circle(points, &brackets[7..brackets.len() - 1])
+        }
+        else if brackets.starts_with("Arc(") && brackets.ends_with(')'){
+            Self::parse_arc(points, &brackets[4..brackets.len() - 1])
+        }
+        else if brackets.starts_with("Line(") && brackets.ends_with(')'){
+            Self::parse_line(points, &brackets[5..brackets.len() - 1])
+        }
+        else {
+            Err(expected("(x y), Circle(...), Arc(...) or Line(...)", brackets))
+        }
+    }
+
+    //Splits the arguments of a primitive into its numbers and an optional trailing cw or ccw
+    fn parse_primitive_arguments(inside: &str) -> Result<(Vec<f32>, bool), String>{
+        let mut args: Vec<&str> = inside.split_whitespace().collect();
+        let clockwise = match args.last() {
+            Some(&"cw") => {
+                args.pop();
+                true
+            }
+            Some(&"ccw") => {
+                args.pop();
+                false
+            }
+            _ => true,
+        };
+        let numbers: Result<Vec<f32>, String> = args.iter().map(|arg| parse_value(arg, "a number")).collect();
+        numbers.map(|numbers| (numbers, clockwise))
+    }
+
+    //Circle(cx cy r [start_angle end_angle] [cw|ccw])
+    fn parse_circle(points: &mut Vec<Point>, inside: &str) -> Result<(), String>{
+        let (numbers, clockwise) = Self::parse_primitive_arguments(inside)?;
+        match numbers[..] {
+            [cx, cy, r] => Self::sample_arc(points, cx, cy, r, r, 0.0, 360.0, clockwise),
+            [cx, cy, r, start, end] => Self::sample_arc(points, cx, cy, r, r, start, end, clockwise),
+            _ => return Err("Circle needs cx cy r and optionally a start and end angle".to_owned()),
+        }
+        Ok(())
+    }
+
+    //Arc(cx cy start_radius end_radius start_angle end_angle [cw|ccw])
+    //Different radii result in a spiral
+    fn parse_arc(points: &mut Vec<Point>, inside: &str) -> Result<(), String>{
+        let (numbers, clockwise) = Self::parse_primitive_arguments(inside)?;
+        match numbers[..] {
+            [cx, cy, r1, r2, start, end] => {
+                Self::sample_arc(points, cx, cy, r1, r2, start, end, clockwise);
+                Ok(())
+            }
+            _ => Err("Arc needs cx cy start_radius end_radius start_angle end_angle".to_owned()),
+        }
+    }
+
+    //Line(x1 y1 x2 y2)
+    fn parse_line(points: &mut Vec<Point>, inside: &str) -> Result<(), String>{
+        let (numbers, _) = Self::parse_primitive_arguments(inside)?;
+        match numbers[..] {
+            [x1, y1, x2, y2] => {
+                let length = ((x2 - x1).powi(2) + (y2 - y1).powi(2)).sqrt();
+                let segments = (length / SAMPLE_DISTANCE).ceil().max(1.0) as usize;
+                for i in 0..=segments {
+                    let t = i as f32 / segments as f32;
+                    Self::push_sample(points, x1 + t * (x2 - x1), y1 + t * (y2 - y1));
+                }
+                Ok(())
+            }
+            _ => Err("Line needs x1 y1 x2 y2".to_owned()),
+        }
+    }
+
+    //Angles are in degrees, starting at the positive x axis
+    //Clockwise is meant on screen, where the y axis points down
+    #[allow(clippy::too_many_arguments)]
+    fn sample_arc(points: &mut Vec<Point>, cx: f32, cy: f32, r1: f32, r2: f32, start: f32, end: f32, clockwise: bool){
+        let mut sweep = end - start;
+        if clockwise && sweep <= 0.0 {
+            sweep += 360.0;
+        } else if !clockwise && sweep >= 0.0 {
+            sweep -= 360.0;
+        }
+        let sweep = sweep.to_radians();
+        let start = start.to_radians();
+        let length = sweep.abs() * (r1.abs() + r2.abs()) / 2.0;
+        let segments = (length / SAMPLE_DISTANCE)
+            .ceil()
+            .max((sweep.abs() / (PI / 8.0)).ceil())
+            .max(1.0) as usize;
+        for i in 0..=segments {
+            let t = i as f32 / segments as f32;
+            let angle = start + t * sweep;
+            let r = r1 + t * (r2 - r1);
+            Self::push_sample(points, cx + r * angle.cos(), cy + r * angle.sin());
+        }
+    }
 
+    //Consecutive primitives usually share their end and start point, which is only added once
+    fn push_sample(points: &mut Vec<Point>, x: f32, y: f32){
+        let point = Point::new(x.round() as i16, y.round() as i16);
+        if let Some(last) = points.last() {
+            if last.x == point.x && last.y == point.y {
+                return;
+            }
         }
+        points.push(point);
     }
 
-    fn parse_points(value: &str) -> Vec<Point> {
+    //Errors come with the byte offset of the faulty point
+    fn parse_points(value: &str) -> Result<Vec<Point>, (usize, String)> {
         let mut points = Vec::new();
+        let mut offset = 0;
         for p in value.split(',') {
-            Self::parse_point(&mut points, p.trim());
+            let start = offset + p.len() - p.trim_start().len();
+            Self::parse_point(&mut points, p.trim()).map_err(|message| (start, message))?;
+            offset += p.len() + 1;
         }
-        points
+        Ok(points)
     }
 
-    fn config_file_content() -> String {
+    //Strokes are separated by '|'
+    pub fn parse_strokes(value: &str) -> Result<Vec<Vec<Point>>, (usize, String)> {
+        let mut strokes = Vec::new();
+        let mut offset = 0;
+        for stroke in value.split('|') {
+            let points = Self::parse_points(stroke).map_err(|(start, message)| (offset + start, message))?;
+            if points.is_empty() {
+                return Err((offset, "a stroke needs points".to_owned()));
+            }
+            strokes.push(points);
+            offset += stroke.len() + 1;
+        }
+        Ok(strokes)
+    }
+
+    pub fn config_dir() -> PathBuf {
         let mut configdir = dirs::config_dir().expect("Did not find config dir");
         configdir.push("traps");
         if !configdir.exists() {
-            std::fs::create_dir(&configdir);
+            let _ = std::fs::create_dir(&configdir);
+        }
+        configdir
+    }
+
+    pub fn config_path() -> PathBuf {
+        let toml_path = Self::config_dir().join(TOML_CONFIG_FILE_NAME);
+        if toml_path.exists() {
+            return toml_path;
+        }
+        Self::config_dir().join(CONFIG_FILE_NAME)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coordinates(value: &str) -> Vec<(i16, i16)> {
+        Config::parse_points(value).unwrap().iter().map(|p| (p.x, p.y)).collect()
+    }
+
+    fn parse(content: &str) -> Result<Config, Vec<Diagnostic>> {
+        Config::parse(content, Path::new("trapsrc")).map(|(config, _)| config)
+    }
+
+    fn diagnostics(content: &str) -> Vec<String> {
+        match Config::parse(content, Path::new("trapsrc")) {
+            Ok((_, warnings)) => warnings,
+            Err(diagnostics) => diagnostics,
         }
-        configdir.push("trapsrc");
-        match configdir.exists() {
-            true => std::fs::read_to_string(configdir).expect("Could not read file"),
-            false => "".to_owned(),
+        .iter()
+        .map(ToString::to_string)
+        .collect()
+    }
+
+    #[test]
+    fn line() {
+        let points = coordinates("(0 0), Line(0 0 100 0), (100 50)");
+        assert_eq!(points.first(), Some(&(0, 0)));
+        assert_eq!(points.iter().filter(|p| **p == (0, 0)).count(), 1);
+        assert!(points.contains(&(60, 0)));
+        assert_eq!(points[points.len() - 2], (100, 0));
+        assert_eq!(points.last(), Some(&(100, 50)));
+    }
+
+    #[test]
+    fn circle() {
+        let points = coordinates("Circle(0 0 50)");
+        assert!(points.len() > 8);
+        assert_eq!(points.first(), Some(&(50, 0)));
+        assert_eq!(points.last(), Some(&(50, 0)));
+        assert!(points.iter().all(|(x, y)| {
+            let r = ((*x as f32).powi(2) + (*y as f32).powi(2)).sqrt();
+            (r - 50.0).abs() < 1.0
+        }));
+        //Clockwise on screen passes the bottom first
+        let bottom = points.iter().position(|p| *p == (0, 50)).unwrap();
+        let top = points.iter().position(|p| *p == (0, -50)).unwrap();
+        assert!(bottom < top);
+    }
+
+    #[test]
+    fn half_circle_counter_clockwise() {
+        let points = coordinates("Circle(100 100 50 180 0 ccw)");
+        assert_eq!(points.first(), Some(&(50, 100)));
+        assert_eq!(points.last(), Some(&(150, 100)));
+        assert!(points.iter().all(|(_, y)| *y >= 100));
+    }
+
+    #[test]
+    fn spiral() {
+        let points = coordinates("Arc(0 0 10 100 0 720)");
+        assert_eq!(points.first(), Some(&(10, 0)));
+        assert_eq!(points.last(), Some(&(100, 0)));
+    }
+
+    #[test]
+    fn area() {
+        match Config::parse_area("between (0 0) (5% 100%)") {
+            Ok(Area::Between(start, end)) => {
+                assert_eq!(start.x, Coordinate::Pixels(0));
+                assert_eq!(end.x, Coordinate::Fraction(0.05));
+                assert_eq!(end.y, Coordinate::Fraction(1.0));
+            }
+            area => panic!("Unexpected area {:?}", area),
         }
+        assert!(matches!(Config::parse_area("smaller (10 20)"), Ok(Area::Smaller(_))));
+        assert!(Config::parse_area("between (0 0)").is_err());
+        assert!(Config::parse_area("around (0 0)").is_err());
+    }
+
+    #[test]
+    fn malformed_area() {
+        assert_eq!(
+            Config::parse_area("smaller (10 abc)").unwrap_err(),
+            "expected pixels or a percentage, found 'abc'"
+        );
+        assert_eq!(Config::parse_area("smaller (10)").unwrap_err(), "expected a point like (x y), found '(10)'");
+        assert_eq!(Config::parse_area("smaller (10 20) x").unwrap_err(), "expected a point like (x y), found 'x'");
+        assert_eq!(Config::parse_area("smaller (10 20").unwrap_err(), "expected a point like (x y), found '(10 20'");
+        assert_eq!(
+            diagnostics("name = left\nstart_area = between (0 0) (5% abc)\npoints = (0 0), (1 1)"),
+            vec!["trapsrc:2:14: error: expected pixels or a percentage, found 'abc'"]
+        );
+    }
+
+    #[test]
+    fn edge() {
+        assert_eq!(Config::parse_edge("top_left"), Some((Edge::TopLeft, DEFAULT_EDGE_MARGIN)));
+        assert_eq!(Config::parse_edge("right 20"), Some((Edge::Right, 20)));
+        assert_eq!(Config::parse_edge("middle"), None);
+    }
+
+    #[test]
+    fn window_scope() {
+        let config = parse(
+            "name = back\npoints = (0 0), (-100 0)\n\
+             [window_class = Alacritty]\n\
+             name = previous-tab\npoints = (0 0), (-100 0)\n\
+             name = vim\nwindow_name = ^vim \npoints = (0 0), (-100 0)\n\
+             []\n\
+             name = forward\npoints = (0 0), (100 0)",
+        )
+        .unwrap();
+        let specific: Vec<(&str, bool)> = config
+            .gestures
+            .iter()
+            .map(|gesture| (gesture.name.as_str(), gesture.is_window_specific()))
+            .collect();
+        assert_eq!(
+            specific,
+            vec![("back", false), ("previous-tab", true), ("vim", true), ("forward", false)]
+        );
+    }
+
+    #[test]
+    fn chained_actions() {
+        let config = parse(
+            "name = close\naction = key: ctrl+w\naction = exec: notify-send \"Tab closed\"\n\
+             action = unknown: value\npoints = (0 0), (0 100)\n\
+             name = reload\naction = builtin: reload\npoints = (0 0), (100 100)",
+        )
+        .unwrap();
+        assert_eq!(
+            config.gestures[0].actions,
+            vec![
+                Action::Key(vec![0xffe3, 'w' as u32]),
+                Action::Exec(vec!["notify-send".to_owned(), "Tab closed".to_owned()]),
+                Action::Shell("unknown: value".to_owned()),
+            ]
+        );
+        assert_eq!(config.gestures[1].actions, vec![Action::Builtin(Builtin::Reload)]);
+    }
+
+    #[test]
+    fn trigger() {
+        assert_eq!(Config::parse_trigger("3"), Some((3, 0)));
+        assert_eq!(
+            Config::parse_trigger("super + shift+2"),
+            Some((2, keysym::SUPER_MASK | keysym::SHIFT_MASK))
+        );
+        assert_eq!(Config::parse_trigger("hyper+3"), None);
+        assert_eq!(Config::parse_trigger("ctrl+"), None);
+    }
+
+    #[test]
+    fn key_bindings() {
+        let config = parse(
+            "key.escape = none\n\
+             key.ctrl+r = reload\n\
+             key.b = gesture: back\n\
+             key.t = exec: xterm\n\
+             key.tab = cancel",
+        )
+        .unwrap();
+        let bindings: Vec<(u32, u16, KeyAction)> = config
+            .key_bindings
+            .into_iter()
+            .map(|binding| (binding.keysym, binding.modifiers, binding.action))
+            .collect();
+        assert_eq!(
+            bindings,
+            vec![
+                ('r' as u32, keysym::CONTROL_MASK, KeyAction::Action(Action::Builtin(Builtin::Reload))),
+                ('b' as u32, 0, KeyAction::Gesture("back".to_owned())),
+                ('t' as u32, 0, KeyAction::Action(Action::Exec(vec!["xterm".to_owned()]))),
+                (0xff09, 0, KeyAction::Cancel),
+            ]
+        );
+        assert_eq!(
+            diagnostics("key.ctrl = hide\nkey.hyper = hide\nkey.q = builtin: exit"),
+            vec![
+                "trapsrc:1:12: error: expected modifiers and one key like ctrl+r, found 'ctrl'",
+                "trapsrc:2:13: error: unknown key in 'hyper'",
+                "trapsrc:3:9: error: expected hide, reload or quit, found 'exit'",
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_settings() {
+        assert_eq!(
+            diagnostics("r = 5\n  fg = #fff"),
+            vec!["trapsrc:2:8: error: expected a color like #rrggbbaa, found '#fff'"]
+        );
+        assert!(parse("# comment\nr = wide").is_err());
+        assert_eq!(parse("bg = #10203040").unwrap().bg, color_to_argb(16, 32, 48, 64));
+        assert_eq!(
+            diagnostics("feedback_time = 1s\nfeedback_shape = yes"),
+            vec![
+                "trapsrc:1:17: error: expected milliseconds, found '1s'",
+                "trapsrc:2:18: error: expected true or false, found 'yes'",
+            ]
+        );
+        assert!(parse("fast_trigger = true").unwrap().fast_trigger);
+        let config = parse("overlay_monitor = pointer\nmonitor_areas = true").unwrap();
+        assert_eq!((config.overlay_monitor, config.monitor_areas), (OverlayMonitor::Pointer, true));
+        assert!(parse("overlay_monitor = left").is_err());
+        let config = parse("trail_antialias = true\ntrail_fade = 300").unwrap();
+        assert_eq!((config.trail_antialias, config.trail_fade), (true, 300));
+        let config = parse("feedback_time = 1000\nfeedback_font = -*-terminus-*-*-*-*-16-*\nfeedback_shape = true").unwrap();
+        assert_eq!((config.feedback_time, config.feedback_font.as_str(), config.feedback_shape), (1000, "-*-terminus-*-*-*-*-16-*", true));
+    }
+
+    #[test]
+    fn diagnostics_with_positions() {
+        assert_eq!(
+            diagnostics(
+                "colour = #ffffffff\n\
+                 name = swipe\nmatcher = fuzzy\npoints = (0 0), (10 x)\n\
+                 name = nothing\n\
+                 stroke_pause\n\
+                 [window_name = (]"
+            ),
+            vec![
+                "trapsrc:1:1: warning: unknown key 'colour'",
+                "trapsrc:3:11: error: expected delta, template or rotating_template, found 'fuzzy'",
+                "trapsrc:4:17: error: expected a coordinate in pixels, found 'x'",
+                "trapsrc:6:1: error: expected key = value",
+                "trapsrc:7:1: error: invalid regular expression '('",
+                "trapsrc:5:1: warning: the gesture has no points and is ignored",
+            ]
+        );
+    }
+
+    #[test]
+    fn host_sections() {
+        let mut loader = Loader::new(Machine {
+            hostname: "laptop".to_owned(),
+            resolution: Some((1920, 1080)),
+        });
+        Config::parse_into(
+            &mut loader,
+            "r = 5\n\
+             [host = desktop]\nr = 8\nname = back\npoints = (0 0), (-100 0)\n\
+             [resolution = 1920x1080]\nname = forward\npoints = (0 0), (100 0)\n\
+             [window_class = Alacritty]\nname = tab\npoints = (0 0), (0 100)\n\
+             [host = laptop]\nname = back\npoints = (0 0), (0 -100)\n\
+             []\n[resolution = wide]\n",
+            Path::new("trapsrc"),
+        );
+        let errors: Vec<String> = loader.diagnostics.iter().map(ToString::to_string).collect();
+        assert_eq!(errors, vec!["trapsrc:16:1: error: expected a resolution like 1920x1080, found 'wide'"]);
+        let names: Vec<(&str, bool)> = loader
+            .config
+            .gestures
+            .iter()
+            .map(|gesture| (gesture.name.as_str(), gesture.is_window_specific()))
+            .collect();
+        assert_eq!(names, vec![("forward", false), ("tab", true), ("back", true)]);
+        assert_eq!(loader.config.r, 5);
+    }
+
+    #[test]
+    fn window_scoped_overrides() {
+        let config = parse(
+            "name = close\npoints = (0 0), (0 100)\n\
+             [window_class = firefox]\nname = close\npoints = (0 0), (100 0)\n\
+             [window_class = Alacritty]\nname = close\npoints = (0 0), (-100 0)\n\
+             name = close\npoints = (0 0), (0 -100)\n\
+             []\nname = close\npoints = (0 0), (100 100)\n",
+        )
+        .unwrap();
+        let gestures: Vec<_> = config
+            .gestures
+            .iter()
+            .map(|gesture| (gesture.window_scope(), (gesture.last().x, gesture.last().y)))
+            .collect();
+        assert_eq!(
+            gestures,
+            vec![
+                (vec![], (100, 100)),
+                (vec![("window_class", "firefox")], (100, 0)),
+                (vec![("window_class", "Alacritty")], (0, -100)),
+            ]
+        );
+    }
+
+    #[test]
+    fn includes() {
+        let dir = std::env::temp_dir().join(format!("traps-include-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("conf.d")).unwrap();
+        std::fs::write(dir.join("base"), "r = 3\nname = back\npoints = (0 0), (-100 0)\n").unwrap();
+        std::fs::write(dir.join("conf.d/a.conf"), "name = up\npoints = (0 0), (0 -100)\n").unwrap();
+        std::fs::write(
+            dir.join("conf.d/b.conf"),
+            "name = back\naction = key: alt+left\npoints = (0 0), (-50 0)\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("loop"), "include = loop\n").unwrap();
+        std::fs::write(
+            dir.join("trapsrc"),
+            "include = base\nname = down\npoints = (0 0), (0 100)\ninclude = conf.d/*.conf\nr = 4\n",
+        )
+        .unwrap();
+
+        let mut loader = Loader::new(Machine::default());
+        loader.load_file(&dir.join("trapsrc"), false);
+        let (config, _) = loader.finish().unwrap();
+        let names: Vec<&str> = config.gestures.iter().map(|gesture| gesture.name.as_str()).collect();
+        assert_eq!(names, vec!["back", "down", "up"]);
+        assert_eq!(config.gestures[0].actions.len(), 1);
+        assert_eq!(config.r, 4);
+        let files: Vec<PathBuf> = ["trapsrc", "base", "conf.d/*.conf", "conf.d/a.conf", "conf.d/b.conf"]
+            .iter()
+            .map(|file| dir.join(file))
+            .collect();
+        assert_eq!(config.files, files);
+
+        let mut loader = Loader::new(Machine::default());
+        loader.load_file(&dir.join("loop"), false);
+        assert_eq!(loader.diagnostics.len(), 1);
+        assert_eq!(loader.diagnostics[0].message, "the file includes itself");
+        assert_eq!(
+            diagnostics("include = /nonexistent/trapsrc"),
+            vec!["trapsrc:1:11: error: '/nonexistent/trapsrc' does not exist"]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn preprocess() {
+        let config = parse(
+            "global_preprocess = dedupe, spacing 10\n\
+             name = raw\npreprocess = none\npoints = (0 0), (0 100)\n\
+             name = default\npoints = (0 0), (100 0)",
+        )
+        .unwrap();
+        assert_eq!(config.preprocessing, Preprocessing::parse("dedupe, spacing 10").unwrap());
+        assert_eq!(
+            diagnostics("name = a\npreprocess = smooth x\npoints = (0 0), (0 1)"),
+            vec!["trapsrc:2:14: error: smooth needs a number of points, found 'x'"]
+        );
+    }
+
+    #[test]
+    fn invalid_primitive() {
+        assert_eq!(
+            Config::parse_points("Circle(0 0), Line(0 0 a 1)").err(),
+            Some((0, "Circle needs cx cy r and optionally a start and end angle".to_owned()))
+        );
+        assert_eq!(
+            Config::parse_points("(0 0), Line(0 0 a 1)").err(),
+            Some((7, "expected a number, found 'a'".to_owned()))
+        );
+        assert!(Config::parse_strokes("(0 0), (1 1) |").is_err());
+    }
+
+    #[test]
+    fn multi_stroke_without_pause() {
+        let gestures = "name = plus\n  points = (0 0), (0 100) | (0 0), (100 0)\nname = line\npoints = (0 0), (0 100)\n";
+        assert_eq!(
+            diagnostics(gestures),
+            vec!["trapsrc:2:3: warning: the gesture 'plus' has several strokes, which need a stroke_pause above 0"]
+        );
+        //The pause may also be set after the gestures
+        assert!(diagnostics(&format!("{}stroke_pause = 300", gestures)).is_empty());
+        //Overridden by a gesture with one stroke
+        assert!(diagnostics(&format!("{}name = plus\npoints = (0 0), (0 100)", gestures)).is_empty());
     }
 }
@@ -0,0 +1,171 @@
+use std::io::prelude::*;
+use std::io::{BufReader, ErrorKind};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::time::Duration;
+
+//Line based protocol: the client sends one command line like "trigger close-tab"
+//The daemon answers with any number of output lines, followed by the result code and a message
+
+pub const OK: i32 = 0;
+pub const UNKNOWN_COMMAND: i32 = 1;
+pub const INVALID_ARGUMENT: i32 = 2;
+pub const FAILED: i32 = 3;
+//Reported by the client, when no daemon answered
+pub const NOT_RUNNING: i32 = 4;
+
+//Clients are read on the thread of the event loop, they send their command line right away
+const TIMEOUT: Duration = Duration::from_millis(10);
+
+//Without a runtime directory, the socket is kept apart from other users in the temporary directory
+pub fn socket_path() -> PathBuf {
+    match dirs::runtime_dir() {
+        Some(dir) => dir.join("traps.sock"),
+        None => std::env::temp_dir().join(format!("traps-{}.sock", unsafe { libc::getuid() })),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Response {
+    pub code: i32,
+    pub message: String,
+    pub lines: Vec<String>,
+}
+
+impl Response {
+    pub fn ok(lines: Vec<String>) -> Response {
+        Response {
+            code: OK,
+            message: "ok".to_owned(),
+            lines,
+        }
+    }
+
+    pub fn error(code: i32, message: &str) -> Response {
+        Response {
+            code,
+            message: message.to_owned(),
+            lines: Vec::new(),
+        }
+    }
+
+    fn to_text(&self) -> String {
+        let mut text = String::new();
+        for line in &self.lines {
+            text.push_str(line);
+            text.push('\n');
+        }
+        text.push_str(&format!("{} {}\n", self.code, self.message));
+        text
+    }
+
+    fn parse(text: &str) -> Option<Response> {
+        let mut lines: Vec<String> = text.lines().map(str::to_owned).collect();
+        let last = lines.pop()?;
+        let (code, message) = last.split_once(' ').unwrap_or((&last, ""));
+        Some(Response {
+            code: code.parse().ok()?,
+            message: message.to_owned(),
+            lines,
+        })
+    }
+}
+
+pub struct ControlSocket {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl ControlSocket {
+    //Fails if another daemon already listens on the socket, a stale socket file is replaced
+    pub fn bind() -> std::io::Result<ControlSocket> {
+        let path = socket_path();
+        if path.exists() {
+            if UnixStream::connect(&path).is_ok() {
+                return Err(std::io::Error::new(
+                    ErrorKind::AddrInUse,
+                    "traps is already running",
+                ));
+            }
+            std::fs::remove_file(&path)?;
+        }
+        let listener = UnixListener::bind(&path)?;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o700))?;
+        listener.set_nonblocking(true)?;
+        Ok(ControlSocket { listener, path })
+    }
+
+    //Accepts a pending client and reads its command line
+    pub fn accept(&self) -> Option<Request> {
+        let (stream, _) = self.listener.accept().ok()?;
+        stream.set_nonblocking(false).ok()?;
+        stream.set_read_timeout(Some(TIMEOUT)).ok()?;
+        stream.set_write_timeout(Some(TIMEOUT)).ok()?;
+        let mut line = String::new();
+        BufReader::new(&stream).read_line(&mut line).ok()?;
+        let line = line.trim();
+        let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+        Some(Request {
+            command: command.to_owned(),
+            argument: argument.trim().to_owned(),
+            stream,
+        })
+    }
+
+    pub fn remove(&self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+impl AsRawFd for ControlSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.listener.as_raw_fd()
+    }
+}
+
+pub struct Request {
+    pub command: String,
+    pub argument: String,
+    stream: UnixStream,
+}
+
+impl Request {
+    pub fn respond(mut self, response: &Response) {
+        let _ = self.stream.write_all(response.to_text().as_bytes());
+    }
+}
+
+//Sends the command line to the daemon and waits for its response
+pub fn send(command: &str) -> Response {
+    let mut stream = match UnixStream::connect(socket_path()) {
+        Ok(stream) => stream,
+        Err(_) => return Response::error(NOT_RUNNING, "traps is not running"),
+    };
+    let mut text = String::new();
+    let sent = stream
+        .write_all(format!("{}\n", command).as_bytes())
+        .and_then(|_| stream.read_to_string(&mut text));
+    match sent {
+        Ok(_) => {
+            Response::parse(&text).unwrap_or_else(|| Response::error(FAILED, "invalid response"))
+        }
+        Err(error) => Response::error(FAILED, &error.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn response() {
+        let response = Response::ok(vec!["close-tab".to_owned(), "reload".to_owned()]);
+        assert_eq!(Response::parse(&response.to_text()), Some(response));
+        let error = Response::error(INVALID_ARGUMENT, "unknown gesture");
+        assert_eq!(error.to_text(), "2 unknown gesture\n");
+        assert_eq!(Response::parse(&error.to_text()), Some(error));
+        assert_eq!(Response::parse(""), None);
+    }
+}
@@ -0,0 +1,324 @@
+use super::backend::{self, Event, InputBackend, Listener};
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::RawFd;
+use std::time::{Duration, Instant};
+use traps::config;
+
+//Records the strokes of the trigger button from the pointer devices in /dev/input
+//The devices are not grabbed, so the presses of the button also reach the focused window
+//Coordinates are the sum of the relative movement since the start, there is no screen and no overlay
+
+const EV_SYN: u16 = 0;
+const EV_KEY: u16 = 1;
+const EV_REL: u16 = 2;
+const REL_X: u16 = 0;
+const REL_Y: u16 = 1;
+//Pointer movement, up to which a press of the trigger button is not a stroke
+const CLICK_DISTANCE: i16 = 4;
+
+pub struct EvdevBackend {
+    //Code of the trigger button
+    button: u16,
+    stroke_pause: Duration,
+    trigger: Option<(u8, u16)>,
+    devices: Vec<RawFd>,
+    position: (i16, i16),
+    watched_fds: Vec<RawFd>,
+    running: bool,
+}
+
+impl EvdevBackend {
+    pub fn new(config: &config::Config) -> Result<EvdevBackend, String> {
+        let (button, modifiers) = config
+            .trigger
+            .ok_or("the evdev backend needs a trigger_button")?;
+        if modifiers != 0 {
+            eprintln!("The evdev backend ignores the modifiers of the trigger button");
+        }
+        Ok(EvdevBackend {
+            button: button_code(button)
+                .ok_or_else(|| format!("the evdev backend has no button {}", button))?,
+            stroke_pause: Duration::from_millis(config.stroke_pause),
+            trigger: config.trigger,
+            devices: Vec::new(),
+            position: (0, 0),
+            watched_fds: Vec::new(),
+            running: false,
+        })
+    }
+
+    //Devices, which report relative movement on both axes
+    fn open_devices(&mut self) {
+        let entries = match std::fs::read_dir("/dev/input") {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        for entry in entries.flatten() {
+            if !entry.file_name().as_bytes().starts_with(b"event") {
+                continue;
+            }
+            let path = match CString::new(entry.path().as_os_str().as_bytes()) {
+                Ok(path) => path,
+                Err(_) => continue,
+            };
+            let fd = unsafe {
+                libc::open(
+                    path.as_ptr(),
+                    libc::O_RDONLY | libc::O_NONBLOCK | libc::O_CLOEXEC,
+                )
+            };
+            if fd < 0 {
+                continue;
+            }
+            let axes = 1 << REL_X | 1 << REL_Y;
+            if relative_axes(fd) & axes == axes {
+                self.devices.push(fd);
+            } else {
+                unsafe {
+                    libc::close(fd);
+                }
+            }
+        }
+    }
+
+    //Adds the movement and keeps the position within the coordinates of a point
+    fn move_by(&mut self, axis: u16, value: i32) {
+        let clamp = |position: i16| {
+            (position as i32 + value).clamp(i16::MIN as i32, i16::MAX as i32) as i16
+        };
+        if axis == REL_X {
+            self.position.0 = clamp(self.position.0);
+        } else {
+            self.position.1 = clamp(self.position.1);
+        }
+    }
+}
+
+impl InputBackend for EvdevBackend {
+    fn init(&mut self) {
+        self.open_devices();
+        if self.devices.is_empty() {
+            eprintln!(
+                "Could not open a pointer device in /dev/input, is the user in the input group?"
+            );
+        }
+    }
+
+    fn watch(&mut self, fd: RawFd) {
+        self.watched_fds.push(fd);
+    }
+
+    fn apply_config(&mut self, config: &config::Config) {
+        self.stroke_pause = Duration::from_millis(config.stroke_pause);
+        if config.trigger != self.trigger {
+            eprintln!("The trigger button changes after a restart");
+        }
+    }
+
+    //Follows the passive mode of the X11 backend, without replaying clicks
+    fn event_loop(&mut self, mut listener: Listener) {
+        self.running = true;
+        let mut pause_deadline: Option<Instant> = None;
+        //Position and time of the trigger press
+        let mut press: Option<(i16, i16, u32)> = None;
+        let mut is_stroke = false;
+        //Set by movement, which is reported with the next synchronization
+        let mut moved = false;
+        while self.running {
+            if pause_deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                pause_deadline = None;
+                listener(Event::Stop, self);
+                listener(Event::Finish, self);
+                continue;
+            }
+            let fds: Vec<RawFd> = self
+                .devices
+                .iter()
+                .chain(self.watched_fds.iter())
+                .copied()
+                .collect();
+            for fd in backend::wait(&fds, pause_deadline) {
+                if self.watched_fds.contains(&fd) {
+                    listener(Event::Readable(fd), self);
+                    continue;
+                }
+                let events = match read_events(fd) {
+                    Some(events) => events,
+                    None => {
+                        //The device was removed
+                        self.devices.retain(|device| *device != fd);
+                        unsafe {
+                            libc::close(fd);
+                        }
+                        continue;
+                    }
+                };
+                for event in events {
+                    let time =
+                        (event.time.tv_sec as u64 * 1000 + event.time.tv_usec as u64 / 1000) as u32;
+                    match (event.type_, event.code) {
+                        (EV_KEY, code) if code == self.button => {
+                            if event.value == 1 {
+                                press = Some((self.position.0, self.position.1, time));
+                                is_stroke = false;
+                            } else if event.value == 0 && press.take().is_some() {
+                                if !is_stroke {
+                                    if pause_deadline.take().is_some() {
+                                        listener(Event::Stop, self);
+                                        listener(Event::Finish, self);
+                                    }
+                                } else if self.stroke_pause.is_zero() {
+                                    listener(Event::Stop, self);
+                                    listener(Event::Finish, self);
+                                } else {
+                                    pause_deadline = Some(Instant::now() + self.stroke_pause);
+                                }
+                            }
+                        }
+                        (EV_REL, axis @ (REL_X | REL_Y)) => {
+                            self.move_by(axis, event.value);
+                            moved = true;
+                        }
+                        (EV_SYN, _) if moved => {
+                            moved = false;
+                            let (x, y, press_time) = match press {
+                                Some(press) => press,
+                                None => continue,
+                            };
+                            let (current_x, current_y) = self.position;
+                            if !is_stroke {
+                                let distance = (current_x - x).abs().max((current_y - y).abs());
+                                if distance < CLICK_DISTANCE {
+                                    continue;
+                                }
+                                is_stroke = true;
+                                if pause_deadline.take().is_some() {
+                                    listener(Event::Stop, self);
+                                } else {
+                                    listener(Event::Focus(traps::WindowInfo::default()), self);
+                                }
+                                listener(Event::Start, self);
+                                listener(Event::Point(x, y, press_time), self);
+                            }
+                            listener(Event::Point(current_x, current_y, time), self);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    fn stop_event_loop(&mut self) {
+        self.running = false;
+    }
+
+    fn screen(&self) -> traps::Screen {
+        traps::Screen::default()
+    }
+
+    fn is_passive(&self) -> bool {
+        true
+    }
+
+    fn is_visible(&self) -> bool {
+        false
+    }
+
+    fn set_visible(&mut self, _visible: bool) {}
+
+    fn show_feedback(&self, _text: &str, _shape: &[Vec<traps::Point>]) {}
+
+    //Would need uinput
+    fn send_key_chord(&mut self, _keysyms: &[u32]) -> bool {
+        false
+    }
+
+    fn click(&mut self, _button: u8) -> bool {
+        false
+    }
+
+    fn machine(&self) -> config::Machine {
+        config::Machine::current()
+    }
+
+    fn window_inspector(&self) -> Option<Box<dyn backend::WindowInspector>> {
+        None
+    }
+}
+
+impl Drop for EvdevBackend {
+    fn drop(&mut self) {
+        for fd in &self.devices {
+            unsafe {
+                libc::close(*fd);
+            }
+        }
+    }
+}
+
+//The evdev code of an X button, the scroll buttons 4 to 7 are no buttons in evdev
+fn button_code(button: u8) -> Option<u16> {
+    match button {
+        1 => Some(0x110),
+        2 => Some(0x112),
+        3 => Some(0x111),
+        8 => Some(0x113),
+        9 => Some(0x114),
+        _ => None,
+    }
+}
+
+//Bit mask of the relative axes of the device, from EVIOCGBIT(EV_REL)
+fn relative_axes(fd: RawFd) -> u8 {
+    let mut bits = 0u8;
+    let request = (2 << 30) | (1 << 16) | ((b'E' as u32) << 8) | (0x20 + EV_REL as u32);
+    let result = unsafe { libc::ioctl(fd, request as _, &mut bits as *mut u8) };
+    if result < 0 {
+        0
+    } else {
+        bits
+    }
+}
+
+//All pending events, None if the device is gone
+fn read_events(fd: RawFd) -> Option<Vec<libc::input_event>> {
+    let size = std::mem::size_of::<libc::input_event>();
+    let mut events = Vec::new();
+    loop {
+        let mut event: libc::input_event = unsafe { std::mem::zeroed() };
+        let length = unsafe {
+            libc::read(
+                fd,
+                &mut event as *mut libc::input_event as *mut libc::c_void,
+                size,
+            )
+        };
+        if length == size as isize {
+            events.push(event);
+            continue;
+        }
+        let would_block =
+            length < 0 && std::io::Error::last_os_error().raw_os_error() == Some(libc::EAGAIN);
+        return if would_block { Some(events) } else { None };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buttons() {
+        assert_eq!(button_code(1), Some(0x110));
+        assert_eq!(button_code(3), Some(0x111));
+        assert_eq!(button_code(4), None);
+        let config = config::Config {
+            trigger: Some((5, 0)),
+            ..Default::default()
+        };
+        assert!(EvdevBackend::new(&config).is_err());
+        assert!(EvdevBackend::new(&config::Config::default()).is_err());
+    }
+}
@@ -0,0 +1,106 @@
+//X keysyms for key names, as used by key chords and key bindings
+//Names are case insensitive, printable characters stand for themselves
+
+pub const SHIFT: u32 = 0xffe1;
+pub const CONTROL: u32 = 0xffe3;
+pub const ALT: u32 = 0xffe9;
+pub const SUPER: u32 = 0xffeb;
+
+//Modifier masks of key and button events, with the values of the X protocol
+pub const SHIFT_MASK: u16 = 0x1;
+pub const CONTROL_MASK: u16 = 0x4;
+//Mod1
+pub const ALT_MASK: u16 = 0x8;
+//Mod4
+pub const SUPER_MASK: u16 = 0x40;
+//Any combination of modifiers, only valid for grabs
+pub const ANY_MODIFIER: u16 = 0x8000;
+
+const NAMES: &[(&str, u32)] = &[
+    ("shift", SHIFT),
+    ("ctrl", CONTROL),
+    ("control", CONTROL),
+    ("alt", ALT),
+    ("super", SUPER),
+    ("win", SUPER),
+    ("altgr", 0xfe03),
+    ("space", 0x20),
+    ("plus", 0x2b),
+    ("minus", 0x2d),
+    ("comma", 0x2c),
+    ("period", 0x2e),
+    ("slash", 0x2f),
+    ("backslash", 0x5c),
+    ("semicolon", 0x3b),
+    ("equal", 0x3d),
+    ("bracketleft", 0x5b),
+    ("bracketright", 0x5d),
+    ("backspace", 0xff08),
+    ("tab", 0xff09),
+    ("return", 0xff0d),
+    ("enter", 0xff0d),
+    ("pause", 0xff13),
+    ("scroll_lock", 0xff14),
+    ("escape", 0xff1b),
+    ("esc", 0xff1b),
+    ("home", 0xff50),
+    ("left", 0xff51),
+    ("up", 0xff52),
+    ("right", 0xff53),
+    ("down", 0xff54),
+    ("page_up", 0xff55),
+    ("prior", 0xff55),
+    ("page_down", 0xff56),
+    ("next", 0xff56),
+    ("end", 0xff57),
+    ("print", 0xff61),
+    ("insert", 0xff63),
+    ("menu", 0xff67),
+    ("num_lock", 0xff7f),
+    ("caps_lock", 0xffe5),
+    ("delete", 0xffff),
+    ("brightness_up", 0x1008ff02),
+    ("brightness_down", 0x1008ff03),
+    ("volume_down", 0x1008ff11),
+    ("volume_mute", 0x1008ff12),
+    ("volume_up", 0x1008ff13),
+    ("play", 0x1008ff14),
+    ("stop", 0x1008ff15),
+    ("previous", 0x1008ff16),
+    ("next_track", 0x1008ff17),
+    ("back", 0x1008ff26),
+    ("forward", 0x1008ff27),
+    ("refresh", 0x1008ff29),
+];
+
+//A key name, F1 to F35, a single character or a keysym in hex like 0xff0d
+pub fn from_name(name: &str) -> Option<u32> {
+    let lowercase = name.to_lowercase();
+    if let Some((_, keysym)) = NAMES.iter().find(|(key, _)| *key == lowercase) {
+        return Some(*keysym);
+    }
+    if let Some(hex) = lowercase.strip_prefix("0x") {
+        return u32::from_str_radix(hex, 16).ok();
+    }
+    if let Some(number) = lowercase.strip_prefix('f') {
+        if let Ok(number @ 1..=35) = number.parse::<u32>() {
+            return Some(0xffbd + number);
+        }
+    }
+    let mut chars = lowercase.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if (' '..='~').contains(&c) => Some(c as u32),
+        _ => None,
+    }
+}
+
+//Keys joined by +, e.g. ctrl+shift+t
+pub fn parse_chord(value: &str) -> Option<Vec<u32>> {
+    if value.trim().is_empty() {
+        return None;
+    }
+    value
+        .split('+')
+        .map(|name| from_name(name.trim()))
+        .collect()
+}
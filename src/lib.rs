@@ -1,4 +1,16 @@
+use regex::Regex;
+use std::cmp::Ordering;
+use std::f32::consts::PI;
 use std::sync::Arc;
+use std::time::Instant;
+
+//Gesture recognition without a display server
+//Frontends feed points into a GestureRecorder and load its gestures through config
+
+pub mod config;
+pub mod keysym;
+pub mod recording;
+pub mod toml_config;
 
 #[derive(Debug, Clone)]
 pub struct Point {
@@ -20,13 +32,32 @@ impl Point {
         yscale: f32,
         tolerance: f32,
     ) -> bool {
+        Point::deviation(p1, p2, p3, p4, xscale, yscale) < tolerance
+    }
+
+    //Largest per-axis difference between the scaled delta p1->p2 and the delta p3->p4
+    pub fn deviation(
+        p1: &Point,
+        p2: &Point,
+        p3: &Point,
+        p4: &Point,
+        xscale: f32,
+        yscale: f32,
+    ) -> f32 {
         let xdif1 = p2.x - p1.x;
         let ydif1 = p2.y - p1.y;
         let xdif2 = p4.x - p3.x;
         let ydif2 = p4.y - p3.y;
 
-        (xdif1 as f32 * xscale - xdif2 as f32).abs() < tolerance
-            && (ydif1 as f32 * yscale - ydif2 as f32).abs() < tolerance
+        (xdif1 as f32 * xscale - xdif2 as f32)
+            .abs()
+            .max((ydif1 as f32 * yscale - ydif2 as f32).abs())
+    }
+
+    pub fn distance(&self, p1: &Point) -> f32 {
+        let dx = (p1.x - self.x) as f32;
+        let dy = (p1.y - self.y) as f32;
+        (dx * dx + dy * dy).sqrt()
     }
 
     pub fn bigger(&self, p1: &Point) -> bool {
@@ -41,20 +72,22 @@ impl Point {
         self.smaller(p2) && self.bigger(p1)
     }
 
-    pub fn bounds(points: &Vec<Point>) -> (i16, i16) {
-        let mut smallx = i16::max_value();
-        let mut smally = i16::max_value();
-        let mut bigx = i16::min_value();
-        let mut bigy = i16::min_value();
+    pub fn bounds(points: &[Point]) -> (i16, i16) {
+        let mut smallx = i16::MAX;
+        let mut smally = i16::MAX;
+        let mut bigx = i16::MIN;
+        let mut bigy = i16::MIN;
         for point in points {
             if point.x > bigx {
                 bigx = point.x;
-            } else if point.x < smallx {
+            }
+            if point.x < smallx {
                 smallx = point.x;
             }
             if point.y > bigy {
                 bigy = point.y;
-            } else if point.y < smally {
+            }
+            if point.y < smally {
                 smally = point.y;
             }
         }
@@ -63,25 +96,46 @@ impl Point {
     }
 }
 
+//Called with the matched gesture and the stroke sequence, returns whether the match was accepted
+pub type Listener = Box<dyn Fn(&GestureMatch, &StrokeContext) -> bool>;
+
 pub struct GestureRecorder {
     pub is_tracking: bool,
     fast_trigger: bool,
+    is_pending: bool,
     points: Vec<Point>,
+    times: Vec<u32>,
+    stroke_starts: Vec<usize>,
+    created: Instant,
     gestures: Vec<Arc<Gesture>>,
     tracked_gestures: Vec<TrackedGesture>,
-    listener: Box<dyn Fn(Arc<Gesture>) -> bool>,
+    min_score: f32,
+    //Used for gestures without their own preprocessing
+    preprocessing: Preprocessing,
+    screen: Screen,
+    window: WindowInfo,
+    listener: Listener,
 }
 
 //Constraints are checked at the end
-//Fast_trigger only works with non-relative gestures
+//Fast_trigger only works with non-relative single-stroke gestures
+//A stroke sequence stays pending after stop, as long as a gesture with more strokes is registered
 impl GestureRecorder {
-    pub fn new(listener: Box<dyn Fn(Arc<Gesture>) -> bool>) -> GestureRecorder {
+    pub fn new(listener: Listener) -> GestureRecorder {
         GestureRecorder {
             is_tracking: false,
             fast_trigger: false,
+            is_pending: false,
             points: Vec::new(),
+            times: Vec::new(),
+            stroke_starts: Vec::new(),
+            created: Instant::now(),
             gestures: Vec::new(),
             tracked_gestures: Vec::new(),
+            min_score: 0.0,
+            preprocessing: Preprocessing::default(),
+            screen: Screen::default(),
+            window: WindowInfo::default(),
             listener,
         }
     }
@@ -97,165 +151,395 @@ impl GestureRecorder {
         self.fast_trigger
     }
 
+    //Matches scoring below this are never executed, regardless of the gesture's own minimum
+    pub fn set_min_score(&mut self, min_score: f32) {
+        self.min_score = min_score;
+    }
+
+    pub fn min_score(&self) -> f32 {
+        self.min_score
+    }
+
+    //Applied to the stroke sequence when it is finished, the fast trigger sees the raw points
+    pub fn set_preprocessing(&mut self, preprocessing: Preprocessing) {
+        self.preprocessing = preprocessing;
+    }
+
+    //Constraints relative to the screen size are resolved against this screen
+    pub fn set_screen(&mut self, screen: Screen) {
+        self.screen = screen;
+    }
+
+    //The window that was focused, when the overlay appeared
+    pub fn set_window(&mut self, window: WindowInfo) {
+        self.window = window;
+    }
+
     pub fn register_gesture(&mut self, mut gesture: Gesture) {
         gesture.calculate_bounds();
+        gesture.calculate_template();
         self.gestures.push(Arc::new(gesture));
     }
 
+    pub fn gestures(&self) -> impl Iterator<Item = &Gesture> {
+        self.gestures.iter().map(|gesture| gesture.as_ref())
+    }
+
+    //Removes all gestures, e.g. before the config is loaded again
+    pub fn clear_gestures(&mut self) {
+        self.gestures.clear();
+        self.tracked_gestures.clear();
+        self.is_pending = false;
+    }
+
+    //Continues the pending stroke sequence or begins a new one
     pub fn start(&mut self) {
-        self.points.clear();
-        for gesture in &self.gestures {
-            self.tracked_gestures
-                .push(TrackedGesture::new(gesture.clone()));
+        if self.is_pending {
+            if self.points.len() > self.stroke_starts.last().copied().unwrap_or(0) {
+                self.stroke_starts.push(self.points.len());
+            }
+            self.is_pending = false;
+        } else {
+            self.points.clear();
+            self.times.clear();
+            self.stroke_starts.clear();
+            self.tracked_gestures.clear();
+            for gesture in &self.gestures {
+                self.tracked_gestures
+                    .push(TrackedGesture::new(gesture.clone()));
+            }
         }
 
         self.is_tracking = true;
     }
 
-    pub fn stop(&mut self) {
-        if !self.fast_trigger && !self.points.is_empty() {
-            let mut found_gesture: Arc<Option<Arc<Gesture>>> = Arc::new(None);
-            let (pwidth, pheight) = Point::bounds(&self.points);
+    //Ends the current stroke
+    //If no registered gesture has more strokes than recorded so far, the sequence is finished
+    pub fn stop(&mut self) -> Vec<GestureMatch> {
+        self.is_tracking = false;
+        if !self.fast_trigger && self.awaits_stroke() {
+            self.is_pending = true;
+            return Vec::new();
+        }
+        self.finish_sequence()
+    }
 
-            let pfirst = self.points.first().unwrap();
-            self.tracked_gestures = self
-                .tracked_gestures
-                .into_iter()
-                .map(|mut tracked_gesture| {
-                    {
-                        if tracked_gesture.is_relative() {
-                            tracked_gesture.determine_scale(pwidth, pheight);
-                        }
-                    }
+    //Finishes a pending stroke sequence, e.g. after the pause between strokes expired
+    pub fn finish(&mut self) -> Vec<GestureMatch> {
+        if self.is_pending {
+            self.finish_sequence()
+        } else {
+            Vec::new()
+        }
+    }
 
-                    let (gesture_matched, tracked_gesture) =
-                        self.check_likeliest_match(tracked_gesture, pfirst);
-                    if gesture_matched {
-                        if let Some(found_gesture_inner) = *found_gesture {
-                            if tracked_gesture.points_count() > found_gesture_inner.points_count() {
-                                found_gesture = Arc::new(Some(tracked_gesture.gesture.clone()));
-                            } else if tracked_gesture.points_count()
-                                == found_gesture_inner.points_count()
-                                && !tracked_gesture.is_relative()
-                            {
-                                found_gesture = Arc::new(Some(tracked_gesture.gesture.clone()));
-                            }
-                        } else {
-                            found_gesture = Arc::new(Some(tracked_gesture.gesture.clone()));
-                        }
-                    }
+    //Drops the stroke sequence without matching it
+    pub fn cancel(&mut self) {
+        self.is_tracking = false;
+        self.is_pending = false;
+        self.points.clear();
+        self.times.clear();
+        self.stroke_starts.clear();
+        self.tracked_gestures.clear();
+    }
 
-                    tracked_gesture
-                })
-                .collect();
+    pub fn is_pending(&self) -> bool {
+        self.is_pending
+    }
+
+    pub fn stroke_count(&self) -> usize {
+        if self.points.is_empty() {
+            0
+        } else {
+            self.stroke_starts.len() + 1
+        }
+    }
 
-            if let Some(found_gesture_inner) = &*found_gesture {
-                self.notify(found_gesture_inner.clone());
+    fn awaits_stroke(&self) -> bool {
+        let stroke_count = self.stroke_count();
+        stroke_count > 0
+            && self
+                .gestures
+                .iter()
+                .any(|gesture| gesture.stroke_count() > stroke_count)
+    }
+
+    //Returns every gesture that matched the stroke sequence, ranked from best to worst
+    //The listener is notified of the accepted matches in that order until it returns true
+    fn finish_sequence(&mut self) -> Vec<GestureMatch> {
+        let mut matches = Vec::new();
+        if !self.fast_trigger && !self.points.is_empty() {
+            matches = self.rank();
+            for gesture_match in &matches {
+                if self.reaches_min_score(gesture_match) && self.notify(gesture_match) {
+                    break;
+                }
             }
         }
+        self.tracked_gestures.clear();
+        self.is_pending = false;
         self.is_tracking = false;
+        matches
     }
 
-    pub fn track(&mut self, plast: Point) {
-        /*if self.fast_trigger {
-            let mut i = 0;
-            while i < self.gestures.len() {
-                let mut tracked_gesture = self.tracked_gestures.get_mut(i).unwrap();
-                let gesture = &tracked_gesture.gesture;
-                let glast = gesture.get_point(tracked_gesture.matched_points);
-
-                if self.points.is_empty() {
-                    tracked_gesture.inc_matched();
-                } else {
-                    let pfirst = self.points.first().unwrap();
-                    let gfirst = gesture.first();
-                    if Point::matched(pfirst, &plast, gfirst, glast, 1.0, 1.0, gesture.tolerance) {
-                        tracked_gesture.inc_matched();
+    //Scores the current stroke sequence against every gesture with the same number of strokes
+    //Ties are broken in favor of window specific gestures, gestures with more points,
+    //then of non-relative gestures
+    pub fn rank(&mut self) -> Vec<GestureMatch> {
+        let mut matches = Vec::new();
+        if self.points.is_empty() {
+            return matches;
+        }
+        let stroke_count = self.stroke_count();
+        let stroke = StrokeContext {
+            points: &self.points,
+            times: &self.times,
+            stroke_starts: &self.stroke_starts,
+            screen: &self.screen,
+            window: &self.window,
+        };
+        //Points and stroke starts for every distinct preprocessing, constraints still see the raw points
+        let mut processed: Vec<(Preprocessing, Vec<Point>, Vec<usize>)> = Vec::new();
+
+        for tracked_gesture in &mut self.tracked_gestures {
+            if tracked_gesture.gesture.stroke_count() != stroke_count {
+                continue;
+            }
+            let preprocessing = match &tracked_gesture.gesture.preprocessing {
+                Some(preprocessing) => preprocessing,
+                None => &self.preprocessing,
+            };
+            let index = match processed
+                .iter()
+                .position(|(other, _, _)| other == preprocessing)
+            {
+                Some(index) => index,
+                None => {
+                    let (points, stroke_starts) =
+                        preprocessing.apply_strokes(&self.points, &self.stroke_starts);
+                    processed.push((preprocessing.clone(), points, stroke_starts));
+                    processed.len() - 1
+                }
+            };
+            let (_, points, stroke_starts) = &processed[index];
+            let score = match tracked_gesture.matcher() {
+                Matcher::Delta => {
+                    if tracked_gesture.is_relative() {
+                        let (pwidth, pheight) = Point::bounds(points);
+                        tracked_gesture.determine_scale(pwidth, pheight);
                     }
+                    Self::check_likeliest_match(&stroke, points, stroke_starts, tracked_gesture)
                 }
-                if tracked_gesture.all_matched() {
-                    if gesture.constraints_matching(&self.points) {
-                        if self.notify(&tracked_gesture.gesture) {
-                            self.stop();
-                            break;
-                        } else {
-                            self.tracked_gestures.remove(i);
-                        }
+                matcher => {
+                    let candidate =
+                        TemplateMatcher::normalize(points, matcher.is_rotation_invariant());
+                    let score = TemplateMatcher::score(
+                        &tracked_gesture.gesture().template,
+                        &candidate,
+                        matcher.is_rotation_invariant(),
+                    );
+                    if score > 0.0 && tracked_gesture.constraints_matching(&stroke) {
+                        Some(score)
                     } else {
-                        self.tracked_gestures.remove(i);
+                        None
                     }
-                } else {
-                    i += 1;
                 }
+            };
+
+            if let Some(score) = score {
+                matches.push(GestureMatch {
+                    gesture: tracked_gesture.gesture.clone(),
+                    score,
+                });
             }
         }
-        self.points.push(plast);*/
+
+        matches.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(Ordering::Equal)
+                .then(
+                    b.gesture
+                        .is_window_specific()
+                        .cmp(&a.gesture.is_window_specific()),
+                )
+                .then(b.gesture.points_count().cmp(&a.gesture.points_count()))
+                .then(a.gesture.is_relative.cmp(&b.gesture.is_relative))
+        });
+        matches
     }
 
-    fn check_likeliest_match(
-        &self,
-        tracked_gesture: TrackedGesture,
-        pfirst: &Point,
-    ) -> (bool, TrackedGesture) {
-        let gfirst = tracked_gesture.first();
-        for point in &self.points {
+    pub fn track(&mut self, plast: Point) {
+        let time = self.created.elapsed().as_millis() as u32;
+        self.track_at(plast, time);
+    }
+
+    //Tracks a point with the time in milliseconds it was recorded at, e.g. the X server time
+    pub fn track_at(&mut self, plast: Point, time: u32) {
+        self.points.push(plast);
+        self.times.push(time);
+        if self.fast_trigger {
+            self.track_fast_trigger();
+        }
+    }
+
+    //Matches the newest point against the next expected point of every delta gesture
+    //The first gesture that is fully matched and accepted by the listener ends the tracking
+    fn track_fast_trigger(&mut self) {
+        let pfirst = self.points.first().unwrap();
+        let plast = self.points.last().unwrap();
+        let mut i = 0;
+        while i < self.tracked_gestures.len() {
+            let tracked_gesture = &mut self.tracked_gestures[i];
+            if tracked_gesture.matcher() != Matcher::Delta
+                || tracked_gesture.gesture.stroke_count() > 1
+            {
+                i += 1;
+                continue;
+            }
             let glast = tracked_gesture.get_point(tracked_gesture.matched_points);
-            if Point::matched(
-                pfirst,
-                &point,
-                gfirst,
-                glast,
-                tracked_gesture.xscale,
-                tracked_gesture.yscale,
-                tracked_gesture.tolerance(),
-            ) {
+            let deviation =
+                Point::deviation(pfirst, plast, tracked_gesture.first(), glast, 1.0, 1.0);
+            if deviation < tracked_gesture.tolerance() {
+                tracked_gesture.deviation += deviation;
                 tracked_gesture.inc_matched();
             }
+
             if tracked_gesture.all_matched() {
-                return (
-                    tracked_gesture.constraints_matching(&self.points),
-                    tracked_gesture,
+                let score = tracked_gesture.score();
+                let gesture_match = GestureMatch {
+                    gesture: self.tracked_gestures.remove(i).gesture,
+                    score,
+                };
+                if self.reaches_min_score(&gesture_match)
+                    && gesture_match
+                        .gesture
+                        .constraints_matching(&self.stroke_context())
+                    && self.notify(&gesture_match)
+                {
+                    self.tracked_gestures.clear();
+                    self.is_tracking = false;
+                    return;
+                }
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    //Every stroke has to match the points of the gesture's stroke with the same index
+    //All deltas are relative to the first point, so the strokes are positioned to each other
+    //The constraints are checked against the raw stroke, the points may be preprocessed
+    fn check_likeliest_match(
+        stroke: &StrokeContext,
+        points: &[Point],
+        stroke_starts: &[usize],
+        tracked_gesture: &mut TrackedGesture,
+    ) -> Option<f32> {
+        let pfirst = points.first().unwrap();
+        tracked_gesture.matched_points = 0;
+        tracked_gesture.deviation = 0.0;
+        let gesture_strokes = stroke_ranges(
+            &tracked_gesture.gesture.stroke_starts,
+            tracked_gesture.points_count(),
+        );
+        let strokes = stroke_ranges(stroke_starts, points.len());
+        for ((start, end), (_, gesture_stroke_end)) in strokes.into_iter().zip(gesture_strokes) {
+            for point in &points[start..end] {
+                if tracked_gesture.matched_points == gesture_stroke_end {
+                    break;
+                }
+                let glast = tracked_gesture.get_point(tracked_gesture.matched_points);
+                let point_deviation = Point::deviation(
+                    pfirst,
+                    point,
+                    tracked_gesture.first(),
+                    glast,
+                    tracked_gesture.xscale,
+                    tracked_gesture.yscale,
                 );
+                if point_deviation < tracked_gesture.tolerance() {
+                    tracked_gesture.deviation += point_deviation;
+                    tracked_gesture.inc_matched();
+                }
+            }
+            if tracked_gesture.matched_points != gesture_stroke_end {
+                return None;
             }
         }
-        (false, tracked_gesture)
+        if !tracked_gesture.all_matched() || !tracked_gesture.constraints_matching(stroke) {
+            return None;
+        }
+        Some(tracked_gesture.score())
+    }
+
+    fn stroke_context(&self) -> StrokeContext<'_> {
+        StrokeContext {
+            points: &self.points,
+            times: &self.times,
+            stroke_starts: &self.stroke_starts,
+            screen: &self.screen,
+            window: &self.window,
+        }
     }
 
-    pub fn notify(&self, gesture: Arc<Gesture>) -> bool {
-        (*self.listener)(gesture)
+    //Both the global minimum and the one of the gesture
+    fn reaches_min_score(&self, gesture_match: &GestureMatch) -> bool {
+        gesture_match.score >= self.min_score
+            && gesture_match.score >= gesture_match.gesture.min_score()
+    }
+
+    pub fn notify(&self, gesture_match: &GestureMatch) -> bool {
+        (*self.listener)(gesture_match, &self.stroke_context())
     }
 }
 
 pub struct GestureAttributes<'a> {
     pub name: &'a str,
-    pub action: &'a str,
+    pub actions: Vec<Action>,
     pub is_relative: bool,
     pub tolerance: f32,
+    pub matcher: Matcher,
+    pub min_score: Option<f32>,
+    pub constraints: Vec<Constraint>,
+    //Replaces the preprocessing of the recorder
+    pub preprocessing: Option<Preprocessing>,
 }
 
-impl<'a> GestureAttributes<'a> {
-    pub fn default() -> GestureAttributes<'a> {
+impl<'a> Default for GestureAttributes<'a> {
+    fn default() -> GestureAttributes<'a> {
         GestureAttributes {
             name: "",
-            action: "",
+            actions: Vec::new(),
             is_relative: false,
             tolerance: GestureRecorder::DEFAULT_TOLERANCE,
+            matcher: Matcher::Delta,
+            min_score: None,
+            constraints: Vec::new(),
+            preprocessing: None,
         }
     }
 }
 
 //Points should start at (0,0)
 //Positioning is possible through constraints
+//Points of further strokes are relative to the first point of the first stroke
 #[derive(Debug, Clone)]
 pub struct Gesture {
     points: Vec<Point>,
+    stroke_starts: Vec<usize>,
     pub is_relative: bool,
     constraints: Vec<Constraint>,
     pub tolerance: f32,
     pub name: String,
-    pub action: String,
+    //Run in order, once the gesture was accepted
+    pub actions: Vec<Action>,
+    pub matcher: Matcher,
+    min_score: Option<f32>,
+    preprocessing: Option<Preprocessing>,
     width: i16,
     height: i16,
+    template: Vec<(f32, f32)>,
 }
 
 impl Gesture {
@@ -263,12 +547,17 @@ impl Gesture {
         Gesture {
             is_relative: attributes.is_relative,
             name: attributes.name.to_owned(),
-            action: attributes.action.to_owned(),
+            actions: attributes.actions.clone(),
             points: Vec::new(),
+            stroke_starts: Vec::new(),
             tolerance: attributes.tolerance,
-            constraints: Vec::new(),
+            constraints: attributes.constraints.clone(),
+            matcher: attributes.matcher,
+            min_score: attributes.min_score,
+            preprocessing: attributes.preprocessing.clone(),
             width: 0,
             height: 0,
+            template: Vec::new(),
         }
     }
 
@@ -278,6 +567,13 @@ impl Gesture {
         self.height = height;
     }
 
+    pub fn calculate_template(&mut self) {
+        if self.matcher != Matcher::Delta {
+            self.template =
+                TemplateMatcher::normalize(&self.points, self.matcher.is_rotation_invariant());
+        }
+    }
+
     pub fn add_points(&mut self, mut points: Vec<Point>) {
         self.points.append(&mut points);
     }
@@ -286,45 +582,51 @@ impl Gesture {
         self.points.push(point);
     }
 
+    pub fn add_constraint(&mut self, constraint: Constraint) {
+        self.constraints.push(constraint);
+    }
+
+    //The window constraints by their config key and value
+    pub fn window_scope(&self) -> Vec<(&str, &str)> {
+        self.constraints
+            .iter()
+            .filter_map(|constraint| match constraint {
+                Constraint::WindowClass(class) => Some(("window_class", class.as_str())),
+                Constraint::WindowName(regex) => Some(("window_name", regex.as_str())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn is_window_specific(&self) -> bool {
+        self.constraints.iter().any(|constraint| {
+            matches!(
+                constraint,
+                Constraint::WindowClass(_) | Constraint::WindowName(_)
+            )
+        })
+    }
+
+    //Appends the points as a new stroke
+    pub fn add_stroke(&mut self, points: Vec<Point>) {
+        if !self.points.is_empty() && !points.is_empty() {
+            self.stroke_starts.push(self.points.len());
+        }
+        self.add_points(points);
+    }
+
+    pub fn stroke_count(&self) -> usize {
+        self.stroke_starts.len() + 1
+    }
+
     pub fn get_point(&self, index: usize) -> &Point {
         self.points.get(index).unwrap()
     }
 
-    pub fn constraints_matching(&self, points: &Vec<Point>) -> bool {
-        let first = points.first().unwrap();
-        let last = points.last().unwrap();
-        for constraint in &self.constraints {
-            let p;
-            let area;
-            match constraint {
-                Constraint::StartArea(a) => {
-                    p = first;
-                    area = a;
-                }
-                Constraint::StopArea(a) => {
-                    p = last;
-                    area = a;
-                }
-            }
-            match area {
-                Area::Between(start, end) => {
-                    if !p.between(start, end) {
-                        return false;
-                    }
-                }
-                Area::Smaller(max) => {
-                    if !p.smaller(max) {
-                        return false;
-                    }
-                }
-                Area::Bigger(min) => {
-                    if !p.bigger(min) {
-                        return false;
-                    }
-                }
-            }
-        }
-        true
+    pub fn constraints_matching(&self, stroke: &StrokeContext) -> bool {
+        self.constraints
+            .iter()
+            .all(|constraint| constraint.matching(stroke))
     }
 
     pub fn first(&self) -> &Point {
@@ -338,12 +640,72 @@ impl Gesture {
     pub fn points_count(&self) -> usize {
         self.points.len()
     }
+
+    //Falls back to the default of the gesture's matcher
+    pub fn min_score(&self) -> f32 {
+        self.min_score
+            .unwrap_or_else(|| self.matcher.default_min_score())
+    }
+
+    //The strokes of the gesture, placed where the stroke sequence began
+    //Relative gestures are scaled to the size of the sequence, like they are matched
+    pub fn shape(&self, stroke: &StrokeContext) -> Vec<Vec<Point>> {
+        let (origin, first) = match (stroke.points.first(), self.points.first()) {
+            (Some(origin), Some(first)) => (origin, first),
+            _ => return Vec::new(),
+        };
+        let (mut xscale, mut yscale) = (1.0, 1.0);
+        if self.is_relative {
+            let (width, height) = Point::bounds(stroke.points);
+            if width != 0 && self.width != 0 {
+                xscale = width as f32 / self.width as f32;
+            }
+            if height != 0 && self.height != 0 {
+                yscale = height as f32 / self.height as f32;
+            }
+        }
+        stroke_ranges(&self.stroke_starts, self.points.len())
+            .into_iter()
+            .map(|(start, end)| {
+                self.points[start..end]
+                    .iter()
+                    .map(|point| {
+                        Point::new(
+                            origin.x + ((point.x - first.x) as f32 * xscale) as i16,
+                            origin.y + ((point.y - first.y) as f32 * yscale) as i16,
+                        )
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+//Start and end index of every stroke
+fn stroke_ranges(stroke_starts: &[usize], len: usize) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    for stroke_start in stroke_starts {
+        ranges.push((start, *stroke_start));
+        start = *stroke_start;
+    }
+    ranges.push((start, len));
+    ranges
+}
+
+#[derive(Debug, Clone)]
+pub struct GestureMatch {
+    pub gesture: Arc<Gesture>,
+    //From 0 (barely matched) to 1 (perfect match)
+    pub score: f32,
 }
 
 #[derive(Debug, Clone)]
 pub struct TrackedGesture {
     gesture: Arc<Gesture>,
     pub matched_points: usize,
+    //Sum of the deviations of the matched points
+    pub deviation: f32,
     pub xscale: f32,
     pub yscale: f32,
 }
@@ -353,6 +715,7 @@ impl TrackedGesture {
         TrackedGesture {
             gesture,
             matched_points: 0,
+            deviation: 0.0,
             xscale: 1.0,
             yscale: 1.0,
         }
@@ -366,9 +729,22 @@ impl TrackedGesture {
         self.matched_points == self.gesture.points.len()
     }
 
+    //Scores a full match by the mean deviation of the matched points relative to the tolerance
+    pub fn score(&self) -> f32 {
+        //The first point always matches without deviation
+        let compared = self.points_count().max(2) - 1;
+        let mean = self.deviation / compared as f32;
+        1.0 - mean / self.tolerance()
+    }
+
+    //A dimension without extent (e.g. a straight swipe) keeps its scale of 1
     pub fn determine_scale(&mut self, pwidth: i16, pheight: i16) {
-        self.xscale = self.gesture.width as f32 / pwidth as f32;
-        self.yscale = self.gesture.height as f32 / pheight as f32;
+        if pwidth != 0 && self.gesture.width != 0 {
+            self.xscale = self.gesture.width as f32 / pwidth as f32;
+        }
+        if pheight != 0 && self.gesture.height != 0 {
+            self.yscale = self.gesture.height as f32 / pheight as f32;
+        }
     }
 
     pub fn is_relative(&self) -> bool {
@@ -395,41 +771,807 @@ impl TrackedGesture {
         self.gesture.get_point(index)
     }
 
-    pub fn constraints_matching(&self, points: &Vec<Point>) -> bool {
-        self.gesture.constraints_matching(points)
+    pub fn constraints_matching(&self, stroke: &StrokeContext) -> bool {
+        self.gesture.constraints_matching(stroke)
     }
 
     pub fn tolerance(&self) -> f32 {
         self.gesture.tolerance
     }
+
+    pub fn matcher(&self) -> Matcher {
+        self.gesture.matcher
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Matcher {
+    //Compares the stroke point by point against the gesture with its tolerance
+    Delta,
+    //Compares the resampled and normalized shape of the stroke against the gesture
+    Template,
+    //Like Template, but the orientation of the stroke is ignored
+    RotatingTemplate,
+}
+
+impl Matcher {
+    pub fn from_name(name: &str) -> Option<Matcher> {
+        match name {
+            "delta" => Some(Matcher::Delta),
+            "template" => Some(Matcher::Template),
+            "rotating_template" => Some(Matcher::RotatingTemplate),
+            _ => None,
+        }
+    }
+
+    pub fn is_rotation_invariant(&self) -> bool {
+        *self == Matcher::RotatingTemplate
+    }
+
+    //Every delta match is already within the tolerance, while templates always score something
+    pub fn default_min_score(&self) -> f32 {
+        match self {
+            Matcher::Delta => 0.0,
+            _ => TemplateMatcher::MIN_SCORE,
+        }
+    }
+}
+
+//Steps applied to every recorded stroke before it is matched, in the order of the fields
+//Raw points arrive at uneven density, depending on the speed of the stroke
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Preprocessing {
+    //Drops points at the position of their predecessor
+    pub dedupe: bool,
+    //Number of points averaged around each point, below 2 nothing is smoothed
+    pub smooth: usize,
+    pub resample: Option<Resample>,
+    //Ramer–Douglas–Peucker epsilon in pixels, 0 keeps all points
+    pub simplify: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Resample {
+    //Points in total with equal distances along the stroke
+    Count(usize),
+    //Distance in pixels between the points
+    Spacing(f32),
+}
+
+impl Preprocessing {
+    //Steps separated by commas: dedupe, smooth N, resample N, spacing D, simplify E, or none
+    pub fn parse(value: &str) -> Result<Preprocessing, String> {
+        let mut preprocessing = Preprocessing::default();
+        for step in value.split(',').map(str::trim) {
+            let (name, argument) = step.split_once(' ').unwrap_or((step, ""));
+            let argument = argument.trim();
+            let number = |description: &str| -> Result<f32, String> {
+                argument
+                    .parse()
+                    .ok()
+                    .filter(|number: &f32| *number > 0.0)
+                    .ok_or_else(|| format!("{} needs {}, found '{}'", name, description, argument))
+            };
+            match name {
+                "none" if value.trim() == "none" => {}
+                "dedupe" if argument.is_empty() => preprocessing.dedupe = true,
+                "smooth" => preprocessing.smooth = number("a number of points")? as usize,
+                "resample" => {
+                    let count = number("a number of points above 1")? as usize;
+                    if count < 2 {
+                        return Err(format!("resample needs a number of points above 1, found '{}'", argument));
+                    }
+                    preprocessing.resample = Some(Resample::Count(count));
+                }
+                "spacing" => preprocessing.resample = Some(Resample::Spacing(number("a distance in pixels")?)),
+                "simplify" => preprocessing.simplify = number("a distance in pixels")?,
+                _ => {
+                    return Err(format!(
+                        "expected dedupe, smooth N, resample N, spacing D, simplify E or none, found '{}'",
+                        step
+                    ))
+                }
+            }
+        }
+        Ok(preprocessing)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        *self != Preprocessing::default()
+    }
+
+    pub fn apply(&self, points: &[Point]) -> Vec<Point> {
+        let mut points = points.to_vec();
+        if self.dedupe {
+            points.dedup_by(|a, b| a.x == b.x && a.y == b.y);
+        }
+        if self.smooth > 1 {
+            points = Self::smooth(&points, self.smooth);
+        }
+        let count = match self.resample {
+            Some(Resample::Count(count)) => Some(count),
+            Some(Resample::Spacing(spacing)) => {
+                let length: f32 = points.windows(2).map(|w| w[0].distance(&w[1])).sum();
+                Some((length / spacing).ceil() as usize + 1)
+            }
+            None => None,
+        };
+        if let Some(count) = count.filter(|_| !points.is_empty()) {
+            points = TemplateMatcher::resample(&points, count.max(2))
+                .into_iter()
+                .map(|(x, y)| Point::new(x.round() as i16, y.round() as i16))
+                .collect();
+        }
+        if self.simplify > 0.0 {
+            points = recording::simplify(&points, self.simplify);
+        }
+        points
+    }
+
+    //Applies the steps to every stroke on its own and returns the points with the new stroke starts
+    pub fn apply_strokes(
+        &self,
+        points: &[Point],
+        stroke_starts: &[usize],
+    ) -> (Vec<Point>, Vec<usize>) {
+        let mut processed = Vec::new();
+        let mut starts = Vec::new();
+        for (start, end) in stroke_ranges(stroke_starts, points.len()) {
+            if !processed.is_empty() {
+                starts.push(processed.len());
+            }
+            processed.append(&mut self.apply(&points[start..end]));
+        }
+        (processed, starts)
+    }
+
+    //Moving average, the first and last point stay in place
+    fn smooth(points: &[Point], window: usize) -> Vec<Point> {
+        let half = window / 2;
+        (0..points.len())
+            .map(|i| {
+                if i == 0 || i == points.len() - 1 {
+                    return points[i].clone();
+                }
+                let start = i.saturating_sub(half);
+                let end = (i + half + 1).min(points.len());
+                let count = (end - start) as f32;
+                let (x, y) = points[start..end]
+                    .iter()
+                    .fold((0.0, 0.0), |(x, y), p| (x + p.x as f32, y + p.y as f32));
+                Point::new((x / count).round() as i16, (y / count).round() as i16)
+            })
+            .collect()
+    }
+}
+
+//Unistroke recognizer in the style of $1
+//Strokes are resampled to a fixed number of points, optionally rotated to their indicative angle,
+//scaled uniformly into a square and centered, so that the size and speed of a stroke do not matter
+pub struct TemplateMatcher;
+
+impl TemplateMatcher {
+    pub const SAMPLE_COUNT: usize = 64;
+    pub const SQUARE_SIZE: f32 = 250.0;
+    pub const MIN_SCORE: f32 = 0.8;
+    const ANGLE_RANGE: f32 = PI / 4.0;
+    const ANGLE_PRECISION: f32 = PI / 90.0;
+
+    pub fn normalize(points: &[Point], rotate: bool) -> Vec<(f32, f32)> {
+        let mut resampled = Self::resample(points, Self::SAMPLE_COUNT);
+        if rotate {
+            let (cx, cy) = Self::centroid(&resampled);
+            let (fx, fy) = resampled[0];
+            resampled = Self::rotate(&resampled, -(fy - cy).atan2(fx - cx));
+        }
+        Self::scale_and_center(&resampled)
+    }
+
+    //Resamples the stroke to count points with equal distances along its path
+    pub fn resample(points: &[Point], count: usize) -> Vec<(f32, f32)> {
+        let mut source: Vec<(f32, f32)> = points.iter().map(|p| (p.x as f32, p.y as f32)).collect();
+        if source.is_empty() {
+            return vec![(0.0, 0.0); count];
+        }
+        let interval = Self::path_length(&source) / (count - 1) as f32;
+        let mut resampled = vec![source[0]];
+        if interval > 0.0 {
+            let mut distance = 0.0;
+            let mut i = 1;
+            while i < source.len() {
+                let (px, py) = source[i - 1];
+                let (x, y) = source[i];
+                let d = ((x - px).powi(2) + (y - py).powi(2)).sqrt();
+                if distance + d >= interval && d > 0.0 {
+                    let t = (interval - distance) / d;
+                    let q = (px + t * (x - px), py + t * (y - py));
+                    resampled.push(q);
+                    source.insert(i, q);
+                    distance = 0.0;
+                } else {
+                    distance += d;
+                }
+                i += 1;
+            }
+        }
+        let last = *source.last().unwrap();
+        resampled.truncate(count);
+        while resampled.len() < count {
+            resampled.push(last);
+        }
+        resampled
+    }
+
+    //Scores the candidate against the template from 0 (unrelated) to 1 (identical)
+    pub fn score(template: &[(f32, f32)], candidate: &[(f32, f32)], rotate: bool) -> f32 {
+        let distance = if rotate {
+            Self::distance_at_best_angle(template, candidate)
+        } else {
+            Self::path_distance(template, candidate)
+        };
+        let half_diagonal = 0.5 * (2.0 * Self::SQUARE_SIZE * Self::SQUARE_SIZE).sqrt();
+        (1.0 - distance / half_diagonal).max(0.0)
+    }
+
+    fn path_length(points: &[(f32, f32)]) -> f32 {
+        points
+            .windows(2)
+            .map(|w| ((w[1].0 - w[0].0).powi(2) + (w[1].1 - w[0].1).powi(2)).sqrt())
+            .sum()
+    }
+
+    fn path_distance(a: &[(f32, f32)], b: &[(f32, f32)]) -> f32 {
+        if a.is_empty() || a.len() != b.len() {
+            return f32::MAX;
+        }
+        let sum: f32 = a
+            .iter()
+            .zip(b)
+            .map(|(p, q)| ((p.0 - q.0).powi(2) + (p.1 - q.1).powi(2)).sqrt())
+            .sum();
+        sum / a.len() as f32
+    }
+
+    //Golden section search for the rotation of the candidate that is closest to the template
+    fn distance_at_best_angle(template: &[(f32, f32)], candidate: &[(f32, f32)]) -> f32 {
+        let phi = 0.5 * (-1.0 + 5.0_f32.sqrt());
+        let mut a = -Self::ANGLE_RANGE;
+        let mut b = Self::ANGLE_RANGE;
+        let mut x1 = phi * a + (1.0 - phi) * b;
+        let mut f1 = Self::path_distance(template, &Self::rotate(candidate, x1));
+        let mut x2 = (1.0 - phi) * a + phi * b;
+        let mut f2 = Self::path_distance(template, &Self::rotate(candidate, x2));
+        while (b - a).abs() > Self::ANGLE_PRECISION {
+            if f1 < f2 {
+                b = x2;
+                x2 = x1;
+                f2 = f1;
+                x1 = phi * a + (1.0 - phi) * b;
+                f1 = Self::path_distance(template, &Self::rotate(candidate, x1));
+            } else {
+                a = x1;
+                x1 = x2;
+                f1 = f2;
+                x2 = (1.0 - phi) * a + phi * b;
+                f2 = Self::path_distance(template, &Self::rotate(candidate, x2));
+            }
+        }
+        f1.min(f2)
+    }
+
+    fn centroid(points: &[(f32, f32)]) -> (f32, f32) {
+        let (sx, sy) = points
+            .iter()
+            .fold((0.0, 0.0), |(sx, sy), (x, y)| (sx + x, sy + y));
+        (sx / points.len() as f32, sy / points.len() as f32)
+    }
+
+    fn rotate(points: &[(f32, f32)], angle: f32) -> Vec<(f32, f32)> {
+        let (cx, cy) = Self::centroid(points);
+        let (sin, cos) = angle.sin_cos();
+        points
+            .iter()
+            .map(|(x, y)| {
+                (
+                    (x - cx) * cos - (y - cy) * sin + cx,
+                    (x - cx) * sin + (y - cy) * cos + cy,
+                )
+            })
+            .collect()
+    }
+
+    //Scales uniformly, so that straight lines keep their shape
+    fn scale_and_center(points: &[(f32, f32)]) -> Vec<(f32, f32)> {
+        let (mut minx, mut miny) = (f32::MAX, f32::MAX);
+        let (mut maxx, mut maxy) = (f32::MIN, f32::MIN);
+        for (x, y) in points {
+            minx = minx.min(*x);
+            miny = miny.min(*y);
+            maxx = maxx.max(*x);
+            maxy = maxy.max(*y);
+        }
+        let size = (maxx - minx).max(maxy - miny);
+        let factor = if size > 0.0 {
+            Self::SQUARE_SIZE / size
+        } else {
+            1.0
+        };
+        let (cx, cy) = Self::centroid(points);
+        points
+            .iter()
+            .map(|(x, y)| ((x - cx) * factor, (y - cy) * factor))
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum Constraint {
     StartArea(Area),
     StopArea(Area),
+    //Durations are in milliseconds
+    MinDuration(u32),
+    MaxDuration(u32),
+    MinLength(f32),
+    //Maximum distance of any point to the line between the first and the last point
+    MaxDeviation(f32),
+    //The stroke starts within the margin of an edge or corner of its monitor
+    StartEdge(Edge, i16),
+    //The stroke starts on the monitor with this name or index
+    Monitor(String),
+    //The focused window has this WM_CLASS instance or class name
+    WindowClass(String),
+    //The title of the focused window matches
+    WindowName(Regex),
+}
+
+impl Constraint {
+    pub fn matching(&self, stroke: &StrokeContext) -> bool {
+        let first = stroke.points.first().unwrap();
+        let last = stroke.points.last().unwrap();
+        match self {
+            Constraint::StartArea(area) => area.contains(first, stroke.screen),
+            Constraint::StopArea(area) => area.contains(last, stroke.screen),
+            Constraint::MinDuration(min) => stroke.duration() >= *min,
+            Constraint::MaxDuration(max) => stroke.duration() <= *max,
+            Constraint::MinLength(min) => stroke.length() >= *min,
+            Constraint::MaxDeviation(max) => stroke.deviation() <= *max,
+            Constraint::StartEdge(edge, margin) => {
+                let monitor = stroke.screen.monitor_at(first);
+                edge.contains(first, &monitor, *margin)
+            }
+            Constraint::Monitor(name) => {
+                stroke
+                    .screen
+                    .all_monitors()
+                    .iter()
+                    .enumerate()
+                    .any(|(index, monitor)| {
+                        (monitor.name == *name || index.to_string() == *name)
+                            && monitor.contains(first)
+                    })
+            }
+            Constraint::WindowClass(class) => stroke.window.class.iter().any(|c| c == class),
+            Constraint::WindowName(name) => name.is_match(&stroke.window.name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Edge {
+    Top,
+    Bottom,
+    Left,
+    Right,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Edge {
+    pub fn from_name(name: &str) -> Option<Edge> {
+        match name {
+            "top" => Some(Edge::Top),
+            "bottom" => Some(Edge::Bottom),
+            "left" => Some(Edge::Left),
+            "right" => Some(Edge::Right),
+            "top_left" => Some(Edge::TopLeft),
+            "top_right" => Some(Edge::TopRight),
+            "bottom_left" => Some(Edge::BottomLeft),
+            "bottom_right" => Some(Edge::BottomRight),
+            _ => None,
+        }
+    }
+
+    pub fn contains(&self, point: &Point, monitor: &Monitor, margin: i16) -> bool {
+        let top = point.y - monitor.y < margin;
+        let bottom = monitor.y + monitor.height - point.y <= margin;
+        let left = point.x - monitor.x < margin;
+        let right = monitor.x + monitor.width - point.x <= margin;
+        match self {
+            Edge::Top => top,
+            Edge::Bottom => bottom,
+            Edge::Left => left,
+            Edge::Right => right,
+            Edge::TopLeft => top && left,
+            Edge::TopRight => top && right,
+            Edge::BottomLeft => bottom && left,
+            Edge::BottomRight => bottom && right,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum Area {
-    Smaller(Point),
-    Bigger(Point),
-    Between(Point, Point),
+    Smaller(ScreenPoint),
+    Bigger(ScreenPoint),
+    Between(ScreenPoint, ScreenPoint),
+}
+
+impl Area {
+    pub fn contains(&self, p: &Point, screen: &Screen) -> bool {
+        let frame = screen.area_frame(p);
+        match self {
+            Area::Between(start, end) => p.between(&start.resolve(&frame), &end.resolve(&frame)),
+            Area::Smaller(max) => p.smaller(&max.resolve(&frame)),
+            Area::Bigger(min) => p.bigger(&min.resolve(&frame)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Coordinate {
+    Pixels(i16),
+    //Fraction of the screen's width or height, e.g. 0.5 for the middle
+    Fraction(f32),
+}
+
+impl Coordinate {
+    pub fn resolve(&self, size: i16) -> i16 {
+        match self {
+            Coordinate::Pixels(pixels) => *pixels,
+            Coordinate::Fraction(fraction) => (fraction * size as f32).round() as i16,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ScreenPoint {
+    pub x: Coordinate,
+    pub y: Coordinate,
+}
+
+impl ScreenPoint {
+    pub fn new(x: Coordinate, y: Coordinate) -> ScreenPoint {
+        ScreenPoint { x, y }
+    }
+
+    pub fn pixels(x: i16, y: i16) -> ScreenPoint {
+        ScreenPoint::new(Coordinate::Pixels(x), Coordinate::Pixels(y))
+    }
+
+    //Relative to the top left corner of the monitor or screen
+    pub fn resolve(&self, frame: &Monitor) -> Point {
+        Point::new(
+            frame.x + self.x.resolve(frame.width),
+            frame.y + self.y.resolve(frame.height),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Screen {
+    pub width: i16,
+    pub height: i16,
+    pub monitors: Vec<Monitor>,
+    //Areas are resolved within the monitor of the point, instead of the whole screen
+    pub monitor_areas: bool,
+}
+
+impl Screen {
+    pub fn new(width: i16, height: i16) -> Screen {
+        Screen {
+            width,
+            height,
+            monitors: Vec::new(),
+            monitor_areas: false,
+        }
+    }
+
+    //Without known monitors, the whole screen is treated as one
+    pub fn all_monitors(&self) -> Vec<Monitor> {
+        if self.monitors.is_empty() {
+            vec![Monitor::new("", 0, 0, self.width, self.height)]
+        } else {
+            self.monitors.clone()
+        }
+    }
+
+    pub fn monitor_at(&self, point: &Point) -> Monitor {
+        self.monitors
+            .iter()
+            .find(|monitor| monitor.contains(point))
+            .cloned()
+            .unwrap_or_else(|| Monitor::new("", 0, 0, self.width, self.height))
+    }
+
+    pub fn area_frame(&self, point: &Point) -> Monitor {
+        if self.monitor_areas {
+            self.monitor_at(point)
+        } else {
+            Monitor::new("", 0, 0, self.width, self.height)
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Monitor {
+    pub name: String,
+    pub x: i16,
+    pub y: i16,
+    pub width: i16,
+    pub height: i16,
+}
+
+impl Monitor {
+    pub fn new(name: &str, x: i16, y: i16, width: i16, height: i16) -> Monitor {
+        Monitor {
+            name: name.to_owned(),
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn contains(&self, point: &Point) -> bool {
+        point.x >= self.x
+            && point.y >= self.y
+            && point.x < self.x + self.width
+            && point.y < self.y + self.height
+    }
+
+    //The point relative to the top left corner of the monitor
+    pub fn relative(&self, point: &Point) -> Point {
+        Point::new(point.x - self.x, point.y - self.y)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct WindowInfo {
+    pub id: u32,
+    //Instance and class name from WM_CLASS
+    pub class: Vec<String>,
+    pub name: String,
+}
+
+//The recorded stroke sequence, which constraints are evaluated against
+pub struct StrokeContext<'a> {
+    pub points: &'a [Point],
+    //Milliseconds at which each point was recorded
+    pub times: &'a [u32],
+    pub stroke_starts: &'a [usize],
+    pub screen: &'a Screen,
+    pub window: &'a WindowInfo,
+}
+
+impl<'a> StrokeContext<'a> {
+    pub fn duration(&self) -> u32 {
+        match (self.times.first(), self.times.last()) {
+            (Some(first), Some(last)) => last.wrapping_sub(*first),
+            _ => 0,
+        }
+    }
+
+    //Sum of the lengths of all strokes, without the jumps between them
+    pub fn length(&self) -> f32 {
+        stroke_ranges(self.stroke_starts, self.points.len())
+            .into_iter()
+            .map(|(start, end)| {
+                self.points[start..end]
+                    .windows(2)
+                    .map(|w| w[0].distance(&w[1]))
+                    .sum::<f32>()
+            })
+            .sum()
+    }
+
+    //The monitor, on which the stroke sequence started
+    pub fn monitor(&self) -> Monitor {
+        self.screen.monitor_at(self.points.first().unwrap())
+    }
+
+    //Top left and bottom right corner of all points
+    pub fn bounding_box(&self) -> (Point, Point) {
+        let mut min = Point::new(i16::MAX, i16::MAX);
+        let mut max = Point::new(i16::MIN, i16::MIN);
+        for point in self.points {
+            min = Point::new(min.x.min(point.x), min.y.min(point.y));
+            max = Point::new(max.x.max(point.x), max.y.max(point.y));
+        }
+        (min, max)
+    }
+
+    pub fn deviation(&self) -> f32 {
+        let first = self.points.first().unwrap();
+        let last = self.points.last().unwrap();
+        let dx = (last.x - first.x) as f32;
+        let dy = (last.y - first.y) as f32;
+        let length = (dx * dx + dy * dy).sqrt();
+        self.points
+            .iter()
+            .map(|p| {
+                if length == 0.0 {
+                    p.distance(first)
+                } else {
+                    (dy * (p.x - first.x) as f32 - dx * (p.y - first.y) as f32).abs() / length
+                }
+            })
+            .fold(0.0, f32::max)
+    }
+}
+
+//What happens when a gesture was accepted
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    //Program and arguments, started without a shell
+    Exec(Vec<String>),
+    //Command line for /bin/sh -c
+    Shell(String),
+    //Keysyms of a key chord, pressed in order and released in reverse order
+    Key(Vec<u32>),
+    //Mouse button, pressed and released at the pointer
+    Click(u8),
+    Builtin(Builtin),
+}
+
+impl Action {
+    //exec:, shell:, key:, click: or builtin:
+    //Values without one of these prefixes are shell commands
+    pub fn parse(value: &str) -> Result<Action, String> {
+        let (kind, argument) = match value.split_once(':') {
+            Some((kind, argument)) => (kind.trim(), argument.trim()),
+            None => return Ok(Action::Shell(value.to_owned())),
+        };
+        match kind {
+            "exec" => match split_arguments(argument) {
+                Some(arguments) if !arguments.is_empty() => Ok(Action::Exec(arguments)),
+                Some(_) => Err("exec needs a program".to_owned()),
+                None => Err(format!("unterminated quote in '{}'", argument)),
+            },
+            "shell" => Ok(Action::Shell(argument.to_owned())),
+            "key" => keysym::parse_chord(argument)
+                .map(Action::Key)
+                .ok_or_else(|| format!("unknown key in '{}'", argument)),
+            "click" => Self::parse_button(argument)
+                .map(Action::Click)
+                .ok_or_else(|| {
+                    format!(
+                        "expected left, middle, right or a button number, found '{}'",
+                        argument
+                    )
+                }),
+            "builtin" => Builtin::from_name(argument)
+                .map(Action::Builtin)
+                .ok_or_else(|| format!("expected hide, reload or quit, found '{}'", argument)),
+            _ => Ok(Action::Shell(value.to_owned())),
+        }
+    }
+
+    fn parse_button(name: &str) -> Option<u8> {
+        match name {
+            "left" => Some(1),
+            "middle" => Some(2),
+            "right" => Some(3),
+            _ => name.parse().ok().filter(|button| *button > 0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Builtin {
+    //Hides the overlay until it is shown again
+    Hide,
+    //Loads the config again
+    Reload,
+    Quit,
+}
+
+impl Builtin {
+    pub fn from_name(name: &str) -> Option<Builtin> {
+        match name {
+            "hide" => Some(Builtin::Hide),
+            "reload" => Some(Builtin::Reload),
+            "quit" => Some(Builtin::Quit),
+            _ => None,
+        }
+    }
+}
+
+//Splits a command line at whitespace, like a shell without expansions
+//Single and double quotes group words, a backslash escapes the next character
+pub fn split_arguments(value: &str) -> Option<Vec<String>> {
+    let mut arguments = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') | (None, '\\') => {
+                current.get_or_insert_with(String::new).push(chars.next()?);
+            }
+            (Some(_), c) => current.get_or_insert_with(String::new).push(c),
+            (None, '"') | (None, '\'') => {
+                quote = Some(c);
+                current.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => arguments.extend(current.take()),
+            (None, c) => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return None;
+    }
+    arguments.extend(current);
+    Some(arguments)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn recording_recorder(accept: bool) -> (GestureRecorder, Rc<RefCell<Vec<String>>>) {
+        let found = Rc::new(RefCell::new(Vec::new()));
+        let found_listener = found.clone();
+        let recorder = GestureRecorder::new(Box::new(move |gesture_match, _| {
+            found_listener
+                .borrow_mut()
+                .push(gesture_match.gesture.name.clone());
+            accept
+        }));
+        (recorder, found)
+    }
+
+    fn gesture(name: &str, is_relative: bool, points: Vec<(i16, i16)>) -> Gesture {
+        let attributes = GestureAttributes {
+            name,
+            is_relative,
+            ..Default::default()
+        };
+        let mut gesture = Gesture::new(&attributes);
+        gesture.add_points(points.into_iter().map(|(x, y)| Point::new(x, y)).collect());
+        gesture
+    }
+
+    fn template_gesture(name: &str, matcher: Matcher, points: Vec<(i16, i16)>) -> Gesture {
+        let mut gesture = gesture(name, false, points);
+        gesture.matcher = matcher;
+        gesture
+    }
+
+    fn stroke(recorder: &mut GestureRecorder, points: &[(i16, i16)]) -> Vec<GestureMatch> {
+        recorder.start();
+        for (x, y) in points {
+            recorder.track(Point::new(*x, *y));
+        }
+        recorder.stop()
+    }
+
     #[test]
     fn first() {
-        let mut recorder = GestureRecorder::new(Box::new(|gesture| {
-            println!("Gesture was found {:?}", gesture);
+        let mut recorder = GestureRecorder::new(Box::new(|gesture_match, _| {
+            println!("Gesture was found {:?}", gesture_match.gesture);
             true
         }));
-        let mut attributes = GestureAttributes::default();
-        attributes.name = "Right-swipe";
-        attributes.action = "some action";
-        attributes.is_relative = true;
+        let attributes = GestureAttributes {
+            name: "Right-swipe",
+            actions: vec![Action::Shell("some action".to_owned())],
+            is_relative: true,
+            ..Default::default()
+        };
         let mut gesture = Gesture::new(&attributes);
         gesture.add_points(vec![Point::new(0, 0), Point::new(-100, 0)]);
         recorder.register_gesture(gesture);
@@ -439,4 +1581,660 @@ mod tests {
         recorder.track(Point::new(500, 488));
         recorder.stop();
     }
+
+    #[test]
+    fn absolute_swipe() {
+        let (mut recorder, found) = recording_recorder(true);
+        recorder.register_gesture(gesture("left", false, vec![(0, 0), (-100, 0)]));
+        recorder.register_gesture(gesture("right", false, vec![(0, 0), (100, 0)]));
+
+        stroke(&mut recorder, &[(500, 500), (550, 505), (598, 510)]);
+        assert_eq!(*found.borrow(), vec!["right"]);
+        assert!(!recorder.is_tracking);
+    }
+
+    #[test]
+    fn no_match() {
+        let (mut recorder, found) = recording_recorder(true);
+        recorder.register_gesture(gesture("down", false, vec![(0, 0), (0, 100)]));
+
+        stroke(&mut recorder, &[(500, 500), (540, 500), (580, 500)]);
+        assert!(found.borrow().is_empty());
+    }
+
+    #[test]
+    fn relative_scaled() {
+        let (mut recorder, found) = recording_recorder(true);
+        recorder.register_gesture(gesture("corner", true, vec![(0, 0), (0, 100), (100, 100)]));
+
+        stroke(
+            &mut recorder,
+            &[(10, 10), (10, 200), (10, 410), (200, 410), (410, 410)],
+        );
+        assert_eq!(*found.borrow(), vec!["corner"]);
+    }
+
+    #[test]
+    fn prefers_more_points() {
+        let (mut recorder, found) = recording_recorder(true);
+        recorder.register_gesture(gesture("down", false, vec![(0, 0), (0, 100)]));
+        recorder.register_gesture(gesture(
+            "down-right",
+            false,
+            vec![(0, 0), (0, 100), (100, 100)],
+        ));
+
+        stroke(&mut recorder, &[(0, 0), (0, 100), (100, 100)]);
+        assert_eq!(*found.borrow(), vec!["down-right"]);
+    }
+
+    #[test]
+    fn fast_trigger() {
+        let (mut recorder, found) = recording_recorder(true);
+        recorder.set_fast_trigger(true);
+        recorder.register_gesture(gesture("down", false, vec![(0, 0), (0, 100)]));
+
+        recorder.start();
+        recorder.track(Point::new(200, 200));
+        recorder.track(Point::new(202, 250));
+        assert!(found.borrow().is_empty());
+        recorder.track(Point::new(205, 301));
+        assert_eq!(*found.borrow(), vec!["down"]);
+        assert!(!recorder.is_tracking);
+
+        recorder.stop();
+        assert_eq!(found.borrow().len(), 1);
+    }
+
+    #[test]
+    fn fast_trigger_min_score() {
+        let (mut recorder, found) = recording_recorder(true);
+        recorder.set_fast_trigger(true);
+        recorder.set_min_score(0.8);
+        recorder.register_gesture(gesture("down", false, vec![(0, 0), (0, 100)]));
+
+        //Deviates by 5 of the tolerance of 20, which scores 0.75
+        recorder.start();
+        recorder.track(Point::new(200, 200));
+        recorder.track(Point::new(205, 300));
+        assert!(found.borrow().is_empty());
+        assert!(recorder.is_tracking);
+        recorder.stop();
+
+        recorder.set_min_score(0.7);
+        recorder.start();
+        recorder.track(Point::new(200, 200));
+        recorder.track(Point::new(205, 300));
+        assert_eq!(*found.borrow(), vec!["down"]);
+    }
+
+    #[test]
+    fn fast_trigger_rejected() {
+        let (mut recorder, found) = recording_recorder(false);
+        recorder.set_fast_trigger(true);
+        recorder.register_gesture(gesture("down", false, vec![(0, 0), (0, 100)]));
+
+        recorder.start();
+        recorder.track(Point::new(200, 200));
+        recorder.track(Point::new(200, 300));
+        recorder.track(Point::new(200, 300));
+        assert_eq!(found.borrow().len(), 1);
+        assert!(recorder.is_tracking);
+    }
+
+    #[test]
+    fn resample_count() {
+        let points = vec![Point::new(0, 0), Point::new(10, 0), Point::new(10, 37)];
+        let resampled = TemplateMatcher::resample(&points, TemplateMatcher::SAMPLE_COUNT);
+        assert_eq!(resampled.len(), TemplateMatcher::SAMPLE_COUNT);
+        assert_eq!(resampled[0], (0.0, 0.0));
+        let (x, y) = resampled[TemplateMatcher::SAMPLE_COUNT - 1];
+        assert!((x - 10.0).abs() < 0.01 && (y - 37.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn template_ignores_size_and_density() {
+        let (mut recorder, found) = recording_recorder(true);
+        recorder.register_gesture(template_gesture(
+            "v",
+            Matcher::Template,
+            vec![(0, 0), (50, 100), (100, 0)],
+        ));
+        recorder.register_gesture(template_gesture(
+            "caret",
+            Matcher::Template,
+            vec![(0, 0), (50, -100), (100, 0)],
+        ));
+
+        let mut points = Vec::new();
+        for i in 0..=40 {
+            points.push((1000 + i * 5, 300 + i * 10));
+        }
+        for i in 1..=7 {
+            points.push((1200 + i * 29, 700 - i * 57));
+        }
+        stroke(&mut recorder, &points);
+        assert_eq!(*found.borrow(), vec!["v"]);
+    }
+
+    #[test]
+    fn template_distinguishes_direction() {
+        let (mut recorder, found) = recording_recorder(true);
+        recorder.register_gesture(template_gesture(
+            "left",
+            Matcher::Template,
+            vec![(0, 0), (-100, 0)],
+        ));
+        recorder.register_gesture(template_gesture(
+            "right",
+            Matcher::Template,
+            vec![(0, 0), (100, 0)],
+        ));
+
+        stroke(&mut recorder, &[(10, 10), (200, 14), (400, 12)]);
+        assert_eq!(*found.borrow(), vec!["right"]);
+    }
+
+    #[test]
+    fn rotating_template() {
+        let square = vec![(0, 0), (100, 0), (100, 100), (0, 100), (0, 0)];
+        let rotated = [(500, 500), (571, 571), (500, 642), (429, 571), (500, 500)];
+
+        let (mut recorder, found) = recording_recorder(true);
+        recorder.register_gesture(template_gesture(
+            "square",
+            Matcher::Template,
+            square.clone(),
+        ));
+        stroke(&mut recorder, &rotated);
+        assert!(found.borrow().is_empty());
+
+        let (mut recorder, found) = recording_recorder(true);
+        recorder.register_gesture(template_gesture(
+            "square",
+            Matcher::RotatingTemplate,
+            square,
+        ));
+        stroke(&mut recorder, &rotated);
+        assert_eq!(*found.borrow(), vec!["square"]);
+    }
+
+    #[test]
+    fn ranked_matches() {
+        let (mut recorder, found) = recording_recorder(true);
+        recorder.register_gesture(gesture("right", false, vec![(0, 0), (100, 0)]));
+        recorder.register_gesture(gesture("right-up", false, vec![(0, 0), (100, -15)]));
+        recorder.register_gesture(gesture("left", false, vec![(0, 0), (-100, 0)]));
+
+        recorder.start();
+        recorder.track(Point::new(0, 0));
+        recorder.track(Point::new(100, -3));
+        let matches = recorder.stop();
+        let names: Vec<&str> = matches.iter().map(|m| m.gesture.name.as_str()).collect();
+        assert_eq!(names, vec!["right", "right-up"]);
+        assert!(matches[0].score > matches[1].score);
+        assert_eq!(*found.borrow(), vec!["right"]);
+    }
+
+    #[test]
+    fn listener_declines_best_match() {
+        let (mut recorder, found) = recording_recorder(false);
+        recorder.register_gesture(gesture("right", false, vec![(0, 0), (100, 0)]));
+        recorder.register_gesture(gesture("right-up", false, vec![(0, 0), (100, -15)]));
+
+        stroke(&mut recorder, &[(0, 0), (100, -3)]);
+        assert_eq!(*found.borrow(), vec!["right", "right-up"]);
+    }
+
+    #[test]
+    fn min_score_rejects_weak_match() {
+        let (mut recorder, found) = recording_recorder(true);
+        recorder.register_gesture(gesture("right", false, vec![(0, 0), (100, 0)]));
+        recorder.set_min_score(0.5);
+
+        stroke(&mut recorder, &[(0, 0), (100, 15)]);
+        assert!(found.borrow().is_empty());
+
+        stroke(&mut recorder, &[(0, 0), (100, 5)]);
+        assert_eq!(*found.borrow(), vec!["right"]);
+    }
+
+    fn multi_stroke_gesture(name: &str, strokes: Vec<Vec<(i16, i16)>>) -> Gesture {
+        let mut gesture = Gesture::new(&GestureAttributes {
+            name,
+            ..Default::default()
+        });
+        for stroke in strokes {
+            gesture.add_stroke(stroke.into_iter().map(|(x, y)| Point::new(x, y)).collect());
+        }
+        gesture
+    }
+
+    #[test]
+    fn cancel_sequence() {
+        let (mut recorder, found) = recording_recorder(true);
+        recorder.register_gesture(multi_stroke_gesture(
+            "cross",
+            vec![vec![(0, 0), (0, 100)], vec![(-50, 50), (50, 50)]],
+        ));
+        stroke(&mut recorder, &[(0, 0), (0, 100)]);
+        assert!(recorder.is_pending());
+        recorder.cancel();
+        assert!(!recorder.is_pending());
+        assert!(recorder.finish().is_empty());
+        assert_eq!(recorder.stroke_count(), 0);
+        assert!(found.borrow().is_empty());
+    }
+
+    #[test]
+    fn multi_stroke() {
+        let (mut recorder, found) = recording_recorder(true);
+        recorder.register_gesture(gesture("down-right", false, vec![(0, 0), (100, 100)]));
+        recorder.register_gesture(multi_stroke_gesture(
+            "x",
+            vec![vec![(0, 0), (100, 100)], vec![(100, 0), (0, 100)]],
+        ));
+        recorder.register_gesture(multi_stroke_gesture(
+            "equals",
+            vec![vec![(0, 0), (100, 0)], vec![(0, 50), (100, 50)]],
+        ));
+
+        stroke(&mut recorder, &[(500, 500), (550, 550), (600, 600)]);
+        assert!(recorder.is_pending());
+        assert!(found.borrow().is_empty());
+
+        let matches = stroke(&mut recorder, &[(598, 502), (550, 550), (503, 597)]);
+        assert!(!recorder.is_pending());
+        assert_eq!(matches.len(), 1);
+        assert_eq!(*found.borrow(), vec!["x"]);
+    }
+
+    #[test]
+    fn multi_stroke_finished_early() {
+        let (mut recorder, found) = recording_recorder(true);
+        recorder.register_gesture(gesture("down-right", false, vec![(0, 0), (100, 100)]));
+        recorder.register_gesture(multi_stroke_gesture(
+            "x",
+            vec![vec![(0, 0), (100, 100)], vec![(100, 0), (0, 100)]],
+        ));
+
+        stroke(&mut recorder, &[(500, 500), (600, 600)]);
+        assert!(recorder.is_pending());
+        recorder.finish();
+        assert!(!recorder.is_pending());
+        assert_eq!(*found.borrow(), vec!["down-right"]);
+
+        assert!(recorder.finish().is_empty());
+        assert_eq!(found.borrow().len(), 1);
+    }
+
+    #[test]
+    fn multi_stroke_wrong_position() {
+        let (mut recorder, found) = recording_recorder(true);
+        recorder.register_gesture(multi_stroke_gesture(
+            "equals",
+            vec![vec![(0, 0), (100, 0)], vec![(0, 50), (100, 50)]],
+        ));
+
+        stroke(&mut recorder, &[(0, 0), (100, 0)]);
+        stroke(&mut recorder, &[(0, 150), (100, 150)]);
+        assert!(found.borrow().is_empty());
+    }
+
+    fn constrained(name: &str, constraint: Constraint) -> Gesture {
+        let mut gesture = gesture(name, false, vec![(0, 0), (0, 100)]);
+        gesture.add_constraint(constraint);
+        gesture
+    }
+
+    #[test]
+    fn start_area() {
+        let (mut recorder, found) = recording_recorder(true);
+        recorder.set_screen(Screen::new(1000, 800));
+        recorder.register_gesture(constrained(
+            "left-edge",
+            Constraint::StartArea(Area::Smaller(ScreenPoint::new(
+                Coordinate::Fraction(0.1),
+                Coordinate::Fraction(1.0),
+            ))),
+        ));
+        recorder.register_gesture(constrained(
+            "middle",
+            Constraint::StartArea(Area::Between(
+                ScreenPoint::pixels(100, 0),
+                ScreenPoint::pixels(900, 800),
+            )),
+        ));
+
+        stroke(&mut recorder, &[(50, 100), (50, 200)]);
+        stroke(&mut recorder, &[(500, 100), (500, 200)]);
+        stroke(&mut recorder, &[(950, 100), (950, 200)]);
+        assert_eq!(*found.borrow(), vec!["left-edge", "middle"]);
+    }
+
+    #[test]
+    fn stop_area() {
+        let (mut recorder, found) = recording_recorder(true);
+        recorder.set_screen(Screen::new(1000, 800));
+        recorder.register_gesture(constrained(
+            "to-bottom",
+            Constraint::StopArea(Area::Bigger(ScreenPoint::new(
+                Coordinate::Pixels(-1),
+                Coordinate::Fraction(0.9),
+            ))),
+        ));
+
+        stroke(&mut recorder, &[(0, 600), (0, 700)]);
+        stroke(&mut recorder, &[(0, 650), (0, 750)]);
+        assert_eq!(*found.borrow(), vec!["to-bottom"]);
+    }
+
+    fn timed_stroke(recorder: &mut GestureRecorder, points: &[(i16, i16, u32)]) {
+        recorder.start();
+        for (x, y, time) in points {
+            recorder.track_at(Point::new(*x, *y), *time);
+        }
+        recorder.stop();
+    }
+
+    #[test]
+    fn duration() {
+        let (mut recorder, found) = recording_recorder(true);
+        recorder.register_gesture(constrained("slow", Constraint::MinDuration(500)));
+        recorder.register_gesture(constrained("fast", Constraint::MaxDuration(200)));
+
+        timed_stroke(
+            &mut recorder,
+            &[(0, 0, 1000), (0, 50, 1300), (0, 100, 1600)],
+        );
+        timed_stroke(
+            &mut recorder,
+            &[(0, 0, 1000), (0, 50, 1050), (0, 100, 1100)],
+        );
+        timed_stroke(
+            &mut recorder,
+            &[(0, 0, 1000), (0, 50, 1150), (0, 100, 1300)],
+        );
+        assert_eq!(*found.borrow(), vec!["slow", "fast"]);
+    }
+
+    #[test]
+    fn length_and_deviation() {
+        let screen = Screen::new(1000, 1000);
+        let points = vec![Point::new(0, 0), Point::new(30, 40), Point::new(60, 0)];
+        let stroke = StrokeContext {
+            points: &points,
+            times: &[],
+            stroke_starts: &[],
+            screen: &screen,
+            window: &WindowInfo::default(),
+        };
+        assert_eq!(stroke.length(), 100.0);
+        assert_eq!(stroke.deviation(), 40.0);
+        assert!(Constraint::MinLength(100.0).matching(&stroke));
+        assert!(!Constraint::MinLength(101.0).matching(&stroke));
+        assert!(!Constraint::MaxDeviation(30.0).matching(&stroke));
+    }
+
+    #[test]
+    fn edges_and_monitors() {
+        let mut screen = Screen::new(3000, 1000);
+        screen.monitors = vec![
+            Monitor::new("DP-1", 0, 0, 1000, 1000),
+            Monitor::new("HDMI-1", 1000, 0, 2000, 1000),
+        ];
+        let points = vec![Point::new(1002, 995), Point::new(1500, 500)];
+        let stroke = StrokeContext {
+            points: &points,
+            times: &[],
+            stroke_starts: &[],
+            screen: &screen,
+            window: &WindowInfo::default(),
+        };
+        assert!(Constraint::StartEdge(Edge::Left, 5).matching(&stroke));
+        assert!(Constraint::StartEdge(Edge::BottomLeft, 5).matching(&stroke));
+        assert!(!Constraint::StartEdge(Edge::Right, 5).matching(&stroke));
+        assert!(!Constraint::StartEdge(Edge::Left, 2).matching(&stroke));
+        assert!(Constraint::Monitor("HDMI-1".to_owned()).matching(&stroke));
+        assert!(Constraint::Monitor("1".to_owned()).matching(&stroke));
+        assert!(!Constraint::Monitor("DP-1".to_owned()).matching(&stroke));
+    }
+
+    #[test]
+    fn window_specific() {
+        let (mut recorder, found) = recording_recorder(true);
+        recorder.register_gesture(gesture("back", false, vec![(0, 0), (0, 100)]));
+        recorder.register_gesture(constrained(
+            "previous-tab",
+            Constraint::WindowClass("Alacritty".to_owned()),
+        ));
+        recorder.register_gesture(constrained(
+            "vim-buffer",
+            Constraint::WindowName(Regex::new("^vim ").unwrap()),
+        ));
+
+        stroke(&mut recorder, &[(500, 0), (500, 100)]);
+        recorder.set_window(WindowInfo {
+            id: 1,
+            class: vec!["alacritty".to_owned(), "Alacritty".to_owned()],
+            name: "htop".to_owned(),
+        });
+        stroke(&mut recorder, &[(500, 0), (500, 100)]);
+        recorder.set_window(WindowInfo {
+            id: 2,
+            class: vec!["xterm".to_owned(), "XTerm".to_owned()],
+            name: "vim lib.rs".to_owned(),
+        });
+        stroke(&mut recorder, &[(500, 0), (500, 100)]);
+        assert_eq!(*found.borrow(), vec!["back", "previous-tab", "vim-buffer"]);
+    }
+
+    #[test]
+    fn listener_context() {
+        let summary = Rc::new(RefCell::new(None));
+        let summary_listener = summary.clone();
+        let mut recorder = GestureRecorder::new(Box::new(move |gesture_match, stroke| {
+            *summary_listener.borrow_mut() = Some((
+                gesture_match.score,
+                stroke.points.first().unwrap().x,
+                stroke.bounding_box(),
+                stroke.duration(),
+            ));
+            true
+        }));
+        recorder.register_gesture(gesture("down", false, vec![(0, 0), (0, 100)]));
+
+        timed_stroke(
+            &mut recorder,
+            &[(20, 10, 100), (15, 60, 150), (30, 110, 250)],
+        );
+        let (score, x, (min, max), duration) = summary.borrow_mut().take().unwrap();
+        assert!(score > 0.0 && score < 1.0);
+        assert_eq!(x, 20);
+        assert_eq!((min.x, min.y, max.x, max.y), (15, 10, 30, 110));
+        assert_eq!(duration, 150);
+    }
+
+    #[test]
+    fn actions() {
+        assert_eq!(
+            Action::parse("exec: convert 'a b.png' \\\"c\\\".png"),
+            Ok(Action::Exec(vec![
+                "convert".to_owned(),
+                "a b.png".to_owned(),
+                "\"c\".png".to_owned()
+            ]))
+        );
+        assert!(Action::parse("exec: 'unterminated").is_err());
+        assert!(Action::parse("exec:").is_err());
+        assert_eq!(
+            Action::parse("echo a:b"),
+            Ok(Action::Shell("echo a:b".to_owned()))
+        );
+        assert_eq!(
+            Action::parse("key: ctrl+shift+T"),
+            Ok(Action::Key(vec![
+                keysym::CONTROL,
+                keysym::SHIFT,
+                't' as u32
+            ]))
+        );
+        assert_eq!(
+            Action::parse("key: ctrl+F5"),
+            Ok(Action::Key(vec![keysym::CONTROL, 0xffc2]))
+        );
+        assert!(Action::parse("key: ctrl+nokey").is_err());
+        assert_eq!(Action::parse("click: right"), Ok(Action::Click(3)));
+        assert!(Action::parse("click: 0").is_err());
+        assert_eq!(
+            Action::parse("builtin: quit"),
+            Ok(Action::Builtin(Builtin::Quit))
+        );
+    }
+
+    pub(crate) fn sample_points(points: &[(i16, i16)]) -> Vec<Point> {
+        points.iter().map(|(x, y)| Point::new(*x, *y)).collect()
+    }
+
+    pub(crate) fn coordinates(points: &[Point]) -> Vec<(i16, i16)> {
+        points.iter().map(|point| (point.x, point.y)).collect()
+    }
+
+    #[test]
+    fn preprocessing() {
+        let preprocessing =
+            Preprocessing::parse("dedupe, smooth 3, resample 5, simplify 1").unwrap();
+        assert_eq!(
+            preprocessing,
+            Preprocessing {
+                dedupe: true,
+                smooth: 3,
+                resample: Some(Resample::Count(5)),
+                simplify: 1.0,
+            }
+        );
+        assert_eq!(Preprocessing::parse("none"), Ok(Preprocessing::default()));
+        assert!(Preprocessing::parse("resample 1").is_err());
+        assert!(Preprocessing::parse("smooth").is_err());
+        assert!(Preprocessing::parse("dedupe, none").is_err());
+
+        let points = sample_points(&[(0, 0), (0, 0), (10, 6), (20, -6), (30, 6), (40, 0)]);
+        let smoothed = Preprocessing::parse("dedupe, smooth 3")
+            .unwrap()
+            .apply(&points);
+        assert_eq!(
+            coordinates(&smoothed),
+            vec![(0, 0), (10, 0), (20, 2), (30, 0), (40, 0)]
+        );
+        let spaced = Preprocessing::parse("spacing 20").unwrap();
+        let (points, stroke_starts) =
+            spaced.apply_strokes(&sample_points(&[(0, 0), (100, 0), (0, 50), (0, 90)]), &[2]);
+        assert_eq!(points.len(), 9);
+        assert_eq!(stroke_starts, vec![6]);
+        assert_eq!(coordinates(&points[..2]), vec![(0, 0), (20, 0)]);
+    }
+
+    #[test]
+    fn preprocessed_strokes() {
+        let (mut recorder, found) = recording_recorder(true);
+        recorder.register_gesture(gesture("right", false, vec![(0, 0), (50, 0), (100, 0)]));
+        //Too fast, no point of the stroke is near the middle of the gesture
+        stroke(&mut recorder, &[(0, 0), (100, 0)]);
+        assert!(found.borrow().is_empty());
+
+        recorder.set_preprocessing(Preprocessing::parse("dedupe, smooth 3, spacing 10").unwrap());
+        stroke(&mut recorder, &[(0, 0), (100, 0)]);
+        stroke(
+            &mut recorder,
+            &[
+                (0, 0),
+                (0, 0),
+                (0, 0),
+                (10, 8),
+                (20, -8),
+                (30, 8),
+                (40, -8),
+                (50, 8),
+                (60, -8),
+                (100, 0),
+            ],
+        );
+        assert_eq!(*found.borrow(), vec!["right", "right"]);
+
+        let attributes = GestureAttributes {
+            name: "raw",
+            preprocessing: Some(Preprocessing::default()),
+            ..Default::default()
+        };
+        let mut raw = Gesture::new(&attributes);
+        raw.add_points(vec![
+            Point::new(0, 0),
+            Point::new(0, 50),
+            Point::new(0, 100),
+        ]);
+        recorder.register_gesture(raw);
+        stroke(&mut recorder, &[(0, 0), (0, 100)]);
+        assert_eq!(found.borrow().len(), 2);
+    }
+
+    #[test]
+    fn gesture_shape() {
+        let screen = Screen::new(1000, 1000);
+        let points = vec![Point::new(500, 500), Point::new(500, 700)];
+        let stroke = StrokeContext {
+            points: &points,
+            times: &[],
+            stroke_starts: &[],
+            screen: &screen,
+            window: &WindowInfo::default(),
+        };
+        let mut absolute = gesture("down", false, vec![(0, 0), (0, 100)]);
+        absolute.add_stroke(vec![Point::new(0, 50), Point::new(20, 50)]);
+        absolute.calculate_bounds();
+        let shape = absolute.shape(&stroke);
+        assert_eq!(coordinates(&shape[0]), vec![(500, 500), (500, 600)]);
+        assert_eq!(coordinates(&shape[1]), vec![(500, 550), (520, 550)]);
+        let mut relative = gesture("down", true, vec![(10, 10), (10, 110)]);
+        relative.calculate_bounds();
+        let shape = relative.shape(&stroke);
+        assert_eq!(shape.len(), 1);
+        assert_eq!(coordinates(&shape[0]), vec![(500, 500), (500, 700)]);
+    }
+
+    #[test]
+    fn monitor_areas() {
+        let (mut recorder, found) = recording_recorder(true);
+        let mut screen = Screen::new(3000, 1000);
+        screen.monitors = vec![
+            Monitor::new("DP-1", 0, 0, 1000, 1000),
+            Monitor::new("HDMI-1", 1000, 0, 2000, 1000),
+        ];
+        screen.monitor_areas = true;
+        recorder.set_screen(screen);
+        recorder.register_gesture(constrained(
+            "left-half",
+            Constraint::StartArea(Area::Smaller(ScreenPoint::new(
+                Coordinate::Fraction(0.5),
+                Coordinate::Fraction(1.0),
+            ))),
+        ));
+        stroke(&mut recorder, &[(400, 100), (400, 200)]);
+        stroke(&mut recorder, &[(1900, 100), (1900, 200)]);
+        stroke(&mut recorder, &[(2100, 100), (2100, 200)]);
+        assert_eq!(*found.borrow(), vec!["left-half", "left-half"]);
+
+        let points = vec![Point::new(1200, 300), Point::new(1500, 700)];
+        let screen = recorder.screen.clone();
+        let stroke = StrokeContext {
+            points: &points,
+            times: &[],
+            stroke_starts: &[],
+            screen: &screen,
+            window: &WindowInfo::default(),
+        };
+        let monitor = stroke.monitor();
+        assert_eq!(monitor.name, "HDMI-1");
+        let end = monitor.relative(points.last().unwrap());
+        assert_eq!((end.x, end.y), (500, 700));
+    }
 }
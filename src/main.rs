@@ -1,112 +1,478 @@
 use clap::{App, Arg};
-use lazy_static::*;
-use std::io::prelude::*;
-use std::path::PathBuf;
-use std::sync::Arc;
-use std::sync::Mutex;
-use std::fs::File;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::cell::RefCell;
+use std::rc::Rc;
+use traps::{config, toml_config};
 
 mod constants;
-mod fifo;
-mod lib;
+mod control;
+mod backend;
+#[cfg(feature = "x11")]
+mod trail;
+#[cfg(feature = "x11")]
 mod ui;
-mod config;
+#[cfg(feature = "x11")]
+mod xtest;
+#[cfg(feature = "x11")]
+mod render;
+#[cfg(feature = "x11")]
+mod randr;
+#[cfg(feature = "evdev")]
+mod evdev;
+#[cfg(feature = "wayland-stub")]
+mod wayland;
+mod watch;
+mod record;
 
 
-const FIFO_PATH: &str = "/tmp/traps0.1.2";
-
 fn main() {
     let matches = make_app().get_matches();
-    if matches.is_present("command") {
-        write_to_fifo(matches.value_of("command").expect("Should have a value"));
-        std::process::exit(0);
+    let backend = match matches.value_of("backend"){
+        Some(name) => match backend::Kind::from_name(name){
+            Some(backend) => Some(backend),
+            None => {
+                let names: Vec<&str> = backend::Kind::ALL.iter().map(|kind| kind.name()).collect();
+                eprintln!("Unknown backend {}, traps was built with: {}", name, names.join(", "));
+                std::process::exit(1);
+            }
+        },
+        None => backend::Kind::ALL.first().copied(),
+    };
+    if let Some(check) = matches.subcommand_matches("check-config") {
+        std::process::exit(check_config(check.value_of("file").map(Path::new), backend));
+    }
+    if let Some(record) = matches.subcommand_matches("record") {
+        let samples = match record.value_of("samples").unwrap_or("1").parse(){
+            Ok(samples) if samples > 0 => samples,
+            _ => {
+                eprintln!("Expected a number of samples above 0");
+                std::process::exit(1);
+            }
+        };
+        std::process::exit(record::record(backend, record.value_of("name").unwrap(), samples, record.value_of("action")));
+    }
+    if let Some(convert) = matches.subcommand_matches("convert-config") {
+        let path = convert
+            .value_of("file")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| config::Config::config_dir().join(config::CONFIG_FILE_NAME));
+        std::process::exit(convert_config(&path, convert.value_of("output").map(Path::new)));
+    }
+    if let Some(command) = matches.values_of("command") {
+        let response = control::send(&command.collect::<Vec<&str>>().join(" "));
+        for line in &response.lines{
+            println!("{}", line);
+        }
+        if response.code != control::OK{
+            eprintln!("{}", response.message);
+        }
+        std::process::exit(response.code);
+    }
+    match backend{
+        Some(backend) => init(backend),
+        None => {
+            eprintln!("traps was built without an input backend");
+            std::process::exit(1);
+        }
+    }
+}
+
+//Prints all errors and warnings, fails if there are errors
+//Without a file, the config is loaded like the daemon does, including the conf.d fragments
+fn check_config(path: Option<&Path>, backend: Option<backend::Kind>) -> i32{
+    let machine = backend.map_or_else(config::Machine::current, backend::Kind::machine);
+    let result = match path{
+        Some(path) => config::Config::load_from(path, machine),
+        None => config::Config::load(machine),
+    };
+    match result{
+        Ok((config, warnings)) => {
+            print_diagnostics(&warnings);
+            let path = path.map(Path::to_owned).unwrap_or_else(config::Config::config_path);
+            println!("{}: {} gestures", path.display(), config.gestures.len());
+            0
+        }
+        Err(diagnostics) => {
+            print_diagnostics(&diagnostics);
+            1
+        }
+    }
+}
+
+//Writes the TOML equivalent of a trapsrc to the output file or stdout
+fn convert_config(path: &Path, output: Option<&Path>) -> i32{
+    let content = match std::fs::read_to_string(path){
+        Ok(content) => content,
+        Err(error) => {
+            eprintln!("Could not read {}: {}", path.display(), error);
+            return 1;
+        }
+    };
+    let toml_config = match toml_config::TomlConfig::from_trapsrc(&content, path){
+        Ok((toml_config, warnings)) => {
+            print_diagnostics(&warnings);
+            toml_config
+        }
+        Err(diagnostics) => {
+            print_diagnostics(&diagnostics);
+            return 1;
+        }
+    };
+    let text = match toml::to_string(&toml_config){
+        Ok(text) => text,
+        Err(error) => {
+            eprintln!("Could not convert the config: {}", error);
+            return 1;
+        }
+    };
+    match output{
+        Some(output) => {
+            if let Err(error) = std::fs::write(output, text){
+                eprintln!("Could not write {}: {}", output.display(), error);
+                return 1;
+            }
+        }
+        None => print!("{}", text),
     }
-    init();
+    0
 }
 
-fn init() {
-    let fifo = fifo::Fifo::new(PathBuf::from(FIFO_PATH));
-    if !fifo.exists(){
-        fifo.create();
+fn print_diagnostics(diagnostics: &[config::Diagnostic]){
+    for diagnostic in diagnostics{
+        eprintln!("{}", diagnostic);
     }
+}
+
+fn init(backend: backend::Kind) {
+    let control = match control::ControlSocket::bind(){
+        Ok(control) => Rc::new(control),
+        Err(error) => {
+            eprintln!("Could not listen on {}: {}", control::socket_path().display(), error);
+            std::process::exit(1);
+        }
+    };
 
     let mut window;
     let mut recorder;
+    let mut watch = None;
+    //Actions of accepted gestures, run once the recorder is done with the stroke sequence
+    let pending: Rc<RefCell<Vec<PendingActions>>> = Rc::new(RefCell::new(Vec::new()));
+    let accepted: Rc<RefCell<Option<Feedback>>> = Rc::new(RefCell::new(None));
     {
 
-        let config = config::Config::new();
+        let config = match config::Config::load(backend.machine()){
+            Ok((config, warnings)) => {
+                print_diagnostics(&warnings);
+                config
+            }
+            Err(diagnostics) => {
+                print_diagnostics(&diagnostics);
+                eprintln!("Not starting, the config has errors");
+                control.remove();
+                std::process::exit(1);
+            }
+        };
+        if config.watch{
+            watch = watch::ConfigWatch::new(&config.files);
+            if watch.is_none(){
+                eprintln!("Could not watch the config for changes");
+            }
+        }
     
-        window = ui::UI::new(&config);
-        recorder = lib::GestureRecorder::new(Box::new(move|gesture|{
-            let value = &gesture.action;
-            Command::new ("/bin/sh").args (&["-c", value]).spawn();
+        window = match backend.create(&config){
+            Ok(window) => window,
+            Err(message) => {
+                eprintln!("Could not start the {} backend: {}", backend.name(), message);
+                control.remove();
+                std::process::exit(1);
+            }
+        };
+        let inspector = window.window_inspector();
+        let pending_listener = pending.clone();
+        let accepted_listener = accepted.clone();
+        recorder = traps::GestureRecorder::new(Box::new(move|gesture_match, stroke|{
+            *accepted_listener.borrow_mut() = Some(Feedback{
+                name: gesture_match.gesture.name.clone(),
+                shape: gesture_match.gesture.shape(stroke),
+            });
+            pending_listener.borrow_mut().push(PendingActions{
+                actions: gesture_match.gesture.actions.clone(),
+                environment: action_environment(gesture_match, stroke, inspector.as_deref()),
+            });
             true
         }));
-        //let mut recorder = recorder_ref.lock().expect("Recorder should not be locked");
-        for gesture in config.gestures{
-            recorder.register_gesture(gesture);
-        }
+        apply_config(&mut recorder, config);
     }
 
 
     window.init();
-    loop {
-        if read_from_fifo(&mut fifo.open_read()){
-            break;
-        }
+    window.watch(control.as_raw_fd());
+    if let Some(watch) = &watch{
+        window.watch(watch.as_raw_fd());
     }
-    window.set_visible(true);
+    recorder.set_screen(window.screen());
 
-    //let recorder_closure = recorder;
-    window.event_loop(Box::new(move |event| {
-        //let mut recorder = recorder_closure;
+    let closure_control = control.clone();
+    window.event_loop(Box::new(move |event, ui| {
         match event{
-            ui::Event::Point(x, y) => {
+            backend::Event::Point(x, y, time) => {
                 if recorder.is_tracking{
-                    recorder.track(lib::Point::new(x, y));
+                    recorder.track_at(traps::Point::new(x, y), time);
                 }
             }
-            ui::Event::Start => {
+            backend::Event::Focus(window) => {
+                recorder.set_screen(ui.screen());
+                recorder.set_window(window);
+            }
+            backend::Event::Start => {
                 recorder.start();
             }
-            ui::Event::Stop => {
+            backend::Event::Stop => {
                 recorder.stop();
-                loop {
-                    if read_from_fifo(&mut fifo.open_read()){
-                        break;
+            }
+            backend::Event::Finish => {
+                recorder.finish();
+                match accepted.borrow_mut().take(){
+                    Some(feedback) => ui.show_feedback(&feedback.name, &feedback.shape),
+                    None => ui.show_feedback("no match", &[]),
+                }
+            }
+            backend::Event::Cancel => {
+                recorder.cancel();
+            }
+            backend::Event::Key(config::KeyAction::Gesture(name)) => {
+                match recorder.gestures().find(|gesture| gesture.name == name){
+                    Some(gesture) => {
+                        //The overlay is used as a launcher, so it gives way to what the gesture runs
+                        ui.set_visible(false);
+                        pending.borrow_mut().push(PendingActions{
+                            actions: gesture.actions.clone(),
+                            environment: vec![("TRAPS_GESTURE_NAME", gesture.name.clone())],
+                        });
                     }
+                    None => eprintln!("Key binding for the unknown gesture {}", name),
                 }
             }
+            backend::Event::Key(config::KeyAction::Action(action)) => {
+                if !matches!(action, traps::Action::Builtin(_)){
+                    ui.set_visible(false);
+                }
+                pending.borrow_mut().push(PendingActions{
+                    actions: vec![action],
+                    environment: Vec::new(),
+                });
+            }
+            //The overlay reports cancelling as its own event
+            backend::Event::Key(config::KeyAction::Cancel) => {}
+            backend::Event::Readable(fd) if fd != closure_control.as_raw_fd() => {
+                if watch.as_ref().is_some_and(|watch| watch.changed()){
+                    print_diagnostics(&reload(&mut recorder, &mut watch, ui).unwrap_or_else(|errors| errors));
+                }
+            }
+            backend::Event::Readable(_) => {
+                if let Some(request) = closure_control.accept(){
+                    let response = handle_request(&request, &mut recorder, &pending, &mut watch, ui);
+                    let quit = request.command == "stop" && response.code == control::OK;
+                    request.respond(&response);
+                    if quit{
+                        quit_daemon(&closure_control);
+                    }
+                }
+            }
+        }
+        let accepted: Vec<PendingActions> = pending.borrow_mut().drain(..).collect();
+        for pending_actions in accepted{
+            run_actions(pending_actions, &mut recorder, &mut watch, ui, &closure_control);
         }
     }));
+    control.remove();
 }
 
-fn read_from_fifo(fifo_file: &mut File) -> bool{
-    let mut buf = String::new();
-    fifo_file.read_to_string(&mut buf);
-    for line in buf.lines(){
-        match line{
-            "show" => {
-                return true;
+//Answers a command of the control socket
+fn handle_request(
+    request: &control::Request,
+    recorder: &mut traps::GestureRecorder,
+    pending: &RefCell<Vec<PendingActions>>,
+    watch: &mut Option<watch::ConfigWatch>,
+    ui: &mut dyn backend::InputBackend,
+) -> control::Response{
+    match request.command.as_str(){
+        "show" => {
+            if ui.is_passive(){
+                return control::Response::error(control::FAILED, "there is no overlay in the passive mode");
             }
-            "stop" => {
-                std::process::exit(0);
+            ui.set_visible(true);
+            control::Response::ok(Vec::new())
+        }
+        "hide" => {
+            ui.set_visible(false);
+            control::Response::ok(Vec::new())
+        }
+        "reload" => {
+            match reload(recorder, watch, ui){
+                Ok(warnings) => control::Response::ok(warnings.iter().map(ToString::to_string).collect()),
+                Err(errors) => control::Response{
+                    lines: errors.iter().map(ToString::to_string).collect(),
+                    ..control::Response::error(control::FAILED, "the config has errors, the old one stays active")
+                },
             }
-            _ => {}
         }
+        "list" => {
+            control::Response::ok(recorder.gestures().map(|gesture| gesture.name.clone()).collect())
+        }
+        "status" => {
+            let mode = if ui.is_passive(){ "passive" } else { "overlay" };
+            control::Response::ok(vec![
+                format!("mode {}", mode),
+                format!("visible {}", ui.is_visible()),
+                format!("tracking {}", recorder.is_tracking),
+                format!("gestures {}", recorder.gestures().count()),
+            ])
+        }
+        "trigger" => {
+            match recorder.gestures().find(|gesture| gesture.name == request.argument){
+                Some(gesture) => {
+                    pending.borrow_mut().push(PendingActions{
+                        actions: gesture.actions.clone(),
+                        environment: vec![("TRAPS_GESTURE_NAME", gesture.name.clone())],
+                    });
+                    control::Response::ok(Vec::new())
+                }
+                None => control::Response::error(control::INVALID_ARGUMENT, "unknown gesture"),
+            }
+        }
+        "stop" => control::Response::ok(Vec::new()),
+        _ => control::Response::error(control::UNKNOWN_COMMAND, "unknown command"),
+    }
+}
+
+fn quit_daemon(control: &control::ControlSocket) -> !{
+    control.remove();
+    std::process::exit(0);
+}
+
+struct PendingActions {
+    actions: Vec<traps::Action>,
+    environment: Vec<(&'static str, String)>,
+}
+
+//Name and shape of the last accepted gesture, shown on the overlay after the stroke sequence
+struct Feedback {
+    name: String,
+    shape: Vec<Vec<traps::Point>>,
+}
+
+//Replaces the gestures and settings of the recorder with those of the config
+fn apply_config(recorder: &mut traps::GestureRecorder, config: config::Config){
+    recorder.set_min_score(config.min_score);
+    recorder.set_fast_trigger(config.fast_trigger);
+    recorder.set_preprocessing(config.preprocessing.clone());
+    recorder.clear_gestures();
+    for gesture in config.gestures{
+        recorder.register_gesture(gesture);
     }
-    false
 }
 
-fn write_to_fifo(text: &str) {
-    let pipe = fifo::Fifo::new(PathBuf::from(FIFO_PATH));
-    if pipe.exists() {
-        let mut file = pipe.open_write();
-        file.write_all(text.as_bytes()).expect("Could not write to fifo ");
-    } else {
-        std::process::exit(1);
+//Replaces the gestures and colors at once, the old config stays active if the new one has errors
+//Returns the warnings or the errors of the config
+//Included files can change with the config, so they are watched anew
+fn reload(
+    recorder: &mut traps::GestureRecorder,
+    watch: &mut Option<watch::ConfigWatch>,
+    ui: &mut dyn backend::InputBackend,
+) -> Result<Vec<config::Diagnostic>, Vec<config::Diagnostic>>{
+    let (config, warnings) = config::Config::load(ui.machine())?;
+    if let Some(watch) = watch{
+        watch.set_files(&config.files);
     }
+    ui.apply_config(&config);
+    apply_config(recorder, config);
+    Ok(warnings)
+}
+
+fn run_actions(
+    pending: PendingActions,
+    recorder: &mut traps::GestureRecorder,
+    watch: &mut Option<watch::ConfigWatch>,
+    ui: &mut dyn backend::InputBackend,
+    control: &control::ControlSocket,
+){
+    for action in pending.actions{
+        match action{
+            traps::Action::Exec(arguments) => {
+                let _ = Command::new(&arguments[0])
+                    .args(&arguments[1..])
+                    .envs(pending.environment.iter().cloned())
+                    .spawn();
+            }
+            traps::Action::Shell(command) => {
+                let _ = Command::new("/bin/sh")
+                    .args(["-c", &command])
+                    .envs(pending.environment.iter().cloned())
+                    .spawn();
+            }
+            traps::Action::Key(keysyms) => {
+                if !ui.send_key_chord(&keysyms){
+                    eprintln!("Could not send the key chord, is XTEST available?");
+                }
+            }
+            traps::Action::Click(button) => {
+                if !ui.click(button){
+                    eprintln!("Could not click, is XTEST available?");
+                }
+            }
+            traps::Action::Builtin(traps::Builtin::Hide) => {
+                ui.set_visible(false);
+            }
+            traps::Action::Builtin(traps::Builtin::Reload) => {
+                print_diagnostics(&reload(recorder, watch, ui).unwrap_or_else(|errors| errors));
+            }
+            traps::Action::Builtin(traps::Builtin::Quit) => {
+                quit_daemon(control);
+            }
+        }
+    }
+}
+
+//Describes the gesture to the action through TRAPS_* environment variables
+fn action_environment(
+    gesture_match: &traps::GestureMatch,
+    stroke: &traps::StrokeContext,
+    inspector: Option<&dyn backend::WindowInspector>,
+) -> Vec<(&'static str, String)> {
+    let start = stroke.points.first().unwrap();
+    let end = stroke.points.last().unwrap();
+    let (min, max) = stroke.bounding_box();
+    //Coordinates relative to the monitor, on which the sequence started
+    let monitor = stroke.monitor();
+    let (monitor_start, monitor_end) = (monitor.relative(start), monitor.relative(end));
+    let window = inspector
+        .map(|inspector| inspector.window_at(start.x, start.y))
+        .unwrap_or_default();
+    vec![
+        ("TRAPS_GESTURE_NAME", gesture_match.gesture.name.clone()),
+        ("TRAPS_SCORE", gesture_match.score.to_string()),
+        ("TRAPS_START_X", start.x.to_string()),
+        ("TRAPS_START_Y", start.y.to_string()),
+        ("TRAPS_END_X", end.x.to_string()),
+        ("TRAPS_END_Y", end.y.to_string()),
+        ("TRAPS_BOUNDS_X", min.x.to_string()),
+        ("TRAPS_BOUNDS_Y", min.y.to_string()),
+        ("TRAPS_BOUNDS_WIDTH", (max.x - min.x).to_string()),
+        ("TRAPS_BOUNDS_HEIGHT", (max.y - min.y).to_string()),
+        ("TRAPS_DURATION", stroke.duration().to_string()),
+        ("TRAPS_MONITOR", monitor.name),
+        ("TRAPS_MONITOR_START_X", monitor_start.x.to_string()),
+        ("TRAPS_MONITOR_START_Y", monitor_start.y.to_string()),
+        ("TRAPS_MONITOR_END_X", monitor_end.x.to_string()),
+        ("TRAPS_MONITOR_END_Y", monitor_end.y.to_string()),
+        ("TRAPS_WINDOW_ID", window.id.to_string()),
+        ("TRAPS_WINDOW_CLASS", window.class.last().cloned().unwrap_or_default()),
+        ("TRAPS_WINDOW_INSTANCE", window.class.first().cloned().unwrap_or_default()),
+        ("TRAPS_WINDOW_NAME", window.name),
+    ]
 }
 
 fn make_app() -> clap::App<'static> {
@@ -114,10 +480,54 @@ fn make_app() -> clap::App<'static> {
         .version(constants::VERSION)
         .author(constants::AUTHOR)
         .about(constants::ABOUT)
+        .arg(
+            Arg::new("backend")
+                .long("backend")
+                .takes_value(true)
+                .global(true)
+                .help("where strokes are read from, defaults to the first compiled one of x11, evdev and wayland"),
+        )
         .arg(
             Arg::new("command")
-                .about("can be one of: show, stop")
+                .help("can be one of: show, hide, reload, list, status, trigger <name>, stop")
                 .required(false)
+                .multiple_values(true)
                 .index(1),
         )
+        .subcommand(
+            App::new("check-config")
+                .about("Prints the errors and warnings of the config")
+                .arg(Arg::new("file").help("defaults to the traps.toml or trapsrc in the config directory")),
+        )
+        .subcommand(
+            App::new("record")
+                .about("Records a gesture on the overlay and appends it to the config")
+                .arg(Arg::new("name").required(true))
+                .arg(
+                    Arg::new("samples")
+                        .short('s')
+                        .long("samples")
+                        .takes_value(true)
+                        .help("how often the gesture is drawn, a tolerance is suggested for more than one"),
+                )
+                .arg(
+                    Arg::new("action")
+                        .short('a')
+                        .long("action")
+                        .takes_value(true)
+                        .help("the action of the gesture, like key: alt+left"),
+                ),
+        )
+        .subcommand(
+            App::new("convert-config")
+                .about("Writes the TOML equivalent of a trapsrc")
+                .arg(Arg::new("file").help("defaults to the trapsrc in the config directory"))
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .takes_value(true)
+                        .help("writes to the file instead of stdout"),
+                ),
+        )
 }
@@ -0,0 +1,47 @@
+use xcb::Connection;
+
+//Monitors through the active CRTCs of RANDR 1.3, named after their first output
+
+pub fn is_supported(conn: &Connection) -> bool {
+    let present = conn
+        .get_extension_data(xcb::randr::id())
+        .map(|data| data.present())
+        .unwrap_or(false);
+    //The version has to be negotiated once, before the screen resources are requested
+    present
+        && xcb::randr::query_version(conn, 1, 3)
+            .get_reply()
+            .map(|reply| (reply.major_version(), reply.minor_version()) >= (1, 3))
+            .unwrap_or(false)
+}
+
+//Active monitors in the order of the server, empty if they could not be queried
+pub fn monitors(conn: &Connection, root: u32) -> Vec<traps::Monitor> {
+    let resources = match xcb::randr::get_screen_resources_current(conn, root).get_reply() {
+        Ok(resources) => resources,
+        Err(_) => return Vec::new(),
+    };
+    let timestamp = resources.config_timestamp();
+    let mut monitors = Vec::new();
+    for crtc in resources.crtcs() {
+        let info = match xcb::randr::get_crtc_info(conn, *crtc, timestamp).get_reply() {
+            Ok(info) => info,
+            Err(_) => continue,
+        };
+        if info.mode() == xcb::NONE || info.outputs().is_empty() {
+            continue;
+        }
+        let name = xcb::randr::get_output_info(conn, info.outputs()[0], timestamp)
+            .get_reply()
+            .map(|output| String::from_utf8_lossy(output.name()).into_owned())
+            .unwrap_or_default();
+        monitors.push(traps::Monitor::new(
+            &name,
+            info.x(),
+            info.y(),
+            info.width() as i16,
+            info.height() as i16,
+        ));
+    }
+    monitors
+}
@@ -0,0 +1,236 @@
+use super::backend::{self, Event};
+use std::cell::RefCell;
+use std::ffi::OsStr;
+use std::io::Write;
+use std::path::Path;
+use std::rc::Rc;
+use traps::config::{self, Config};
+use traps::toml_config::{TomlAction, TomlConfig, TomlGesture, TomlPoints};
+use traps::{recording, Action, Point};
+
+//Largest distance in pixels of a dropped point from the simplified stroke
+const SIMPLIFY_EPSILON: f32 = 4.0;
+
+//Shows the overlay, until the gesture was drawn sample_count times, and appends it to the config
+pub fn record(
+    backend: Option<backend::Kind>,
+    name: &str,
+    sample_count: usize,
+    action: Option<&str>,
+) -> i32 {
+    let backend = match backend {
+        Some(backend) => backend,
+        None => {
+            eprintln!("traps was built without an input backend");
+            return 1;
+        }
+    };
+    if let Some(Err(message)) = action.map(Action::parse) {
+        eprintln!("Invalid action: {}", message);
+        return 1;
+    }
+    let mut config = match Config::load(backend.machine()) {
+        Ok((config, _)) => config,
+        Err(_) => {
+            eprintln!("The config has errors, recording with the default colors");
+            Config::default()
+        }
+    };
+    //Samples are drawn on the overlay, even if the daemon uses a trigger button
+    config.trigger = None;
+
+    let samples: Rc<RefCell<Vec<Vec<Vec<Point>>>>> = Rc::new(RefCell::new(Vec::new()));
+    let recorded = samples.clone();
+    let mut sample: Vec<Vec<Point>> = Vec::new();
+    let mut window = match backend.create(&config) {
+        Ok(window) => window,
+        Err(message) => {
+            eprintln!(
+                "Could not start the {} backend: {}",
+                backend.name(),
+                message
+            );
+            return 1;
+        }
+    };
+    if window.is_passive() {
+        eprintln!("The {} backend has no overlay to record on", backend.name());
+        return 1;
+    }
+    window.init();
+    window.set_visible(true);
+    eprintln!("Draw the gesture {} times, escape cancels", sample_count);
+    window.event_loop(Box::new(move |event, ui| match event {
+        Event::Start => sample.push(Vec::new()),
+        Event::Point(x, y, _) => {
+            if let Some(stroke) = sample.last_mut() {
+                stroke.push(Point::new(x, y));
+            }
+        }
+        Event::Finish => {
+            let strokes: Vec<Vec<Point>> = std::mem::take(&mut sample)
+                .into_iter()
+                .filter(|stroke| !stroke.is_empty())
+                .collect();
+            if strokes.is_empty() {
+                ui.set_visible(true);
+                return;
+            }
+            let mut recorded = recorded.borrow_mut();
+            recorded.push(strokes);
+            eprintln!("Recorded sample {} of {}", recorded.len(), sample_count);
+            if recorded.len() < sample_count {
+                ui.set_visible(true);
+            } else {
+                ui.stop_event_loop();
+            }
+        }
+        Event::Cancel => ui.stop_event_loop(),
+        _ => {}
+    }));
+
+    let samples = samples.borrow();
+    if samples.len() < sample_count {
+        eprintln!("Recording cancelled");
+        return 1;
+    }
+    let (template, tolerance) = match recording::pick_template(&samples) {
+        Some(template) => template,
+        None => {
+            eprintln!("The samples have a different number of strokes");
+            return 1;
+        }
+    };
+    let strokes: Vec<Vec<Point>> = recording::normalize(&samples[template])
+        .iter()
+        .map(|stroke| recording::simplify(stroke, SIMPLIFY_EPSILON))
+        .collect();
+    if let Some(tolerance) = tolerance {
+        eprintln!("Suggested tolerance: {}", tolerance);
+    }
+    let path = Config::config_path();
+    match append(&path, name, &strokes, tolerance, action) {
+        Ok(block) => {
+            print!("{}", block);
+            eprintln!("Appended the gesture to {}", path.display());
+            0
+        }
+        Err(error) => {
+            eprintln!("Could not write {}: {}", path.display(), error);
+            1
+        }
+    }
+}
+
+fn points_text(strokes: &[Vec<Point>]) -> String {
+    strokes
+        .iter()
+        .map(|stroke| {
+            stroke
+                .iter()
+                .map(|point| format!("({} {})", point.x, point.y))
+                .collect::<Vec<String>>()
+                .join(", ")
+        })
+        .collect::<Vec<String>>()
+        .join(" | ")
+}
+
+//Appends the gesture in the format of the file and returns the appended text
+fn append(
+    path: &Path,
+    name: &str,
+    strokes: &[Vec<Point>],
+    tolerance: Option<f32>,
+    action: Option<&str>,
+) -> std::io::Result<String> {
+    let content = std::fs::read_to_string(path).unwrap_or_default();
+    let mut block = String::new();
+    if !content.is_empty() {
+        block.push_str(if content.ends_with('\n') {
+            "\n"
+        } else {
+            "\n\n"
+        });
+    }
+    if path.file_name() == Some(OsStr::new(config::TOML_CONFIG_FILE_NAME)) {
+        let gesture = TomlGesture {
+            name: name.to_owned(),
+            tolerance,
+            points: TomlPoints::Text(points_text(strokes)),
+            actions: action.map(TomlAction::from_trapsrc).into_iter().collect(),
+            ..Default::default()
+        };
+        let toml_config = TomlConfig {
+            gestures: vec![gesture],
+            ..Default::default()
+        };
+        block.push_str(&toml::to_string(&toml_config).map_err(|error| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, error.to_string())
+        })?);
+    } else {
+        //The gesture would otherwise only apply to the section at the end of the file
+        if content
+            .lines()
+            .any(|line| line.trim_start().starts_with('['))
+        {
+            block.push_str("[]\n");
+        }
+        block.push_str(&format!("name = {}\n", name));
+        if let Some(action) = action {
+            block.push_str(&format!("action = {}\n", action));
+        }
+        if let Some(tolerance) = tolerance {
+            block.push_str(&format!("tolerance = {}\n", tolerance));
+        }
+        block.push_str(&format!("points = {}\n", points_text(strokes)));
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    file.write_all(block.as_bytes())?;
+    Ok(block)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn append_gesture() {
+        let dir = std::env::temp_dir().join(format!("traps-record-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let strokes = vec![
+            vec![Point::new(0, 0), Point::new(-100, 0)],
+            vec![Point::new(0, 20), Point::new(0, 80)],
+        ];
+        let trapsrc = dir.join("trapsrc");
+        std::fs::write(
+            &trapsrc,
+            "[window_class = Alacritty]\nname = tab\npoints = (0 0), (0 100)",
+        )
+        .unwrap();
+        append(
+            &trapsrc,
+            "back",
+            &strokes,
+            Some(25.0),
+            Some("key: alt+left"),
+        )
+        .unwrap();
+        let (config, _) = Config::load_from(&trapsrc, config::Machine::default()).unwrap();
+        assert_eq!(config.gestures[1].name, "back");
+        assert_eq!(config.gestures[1].tolerance, 25.0);
+        assert_eq!(config.gestures[1].stroke_count(), 2);
+        assert!(!config.gestures[1].is_window_specific());
+
+        let toml = dir.join(config::TOML_CONFIG_FILE_NAME);
+        append(&toml, "back", &strokes, None, Some("key: alt+left")).unwrap();
+        append(&toml, "forward", &strokes[..1], None, None).unwrap();
+        let (config, _) = Config::load_from(&toml, config::Machine::default()).unwrap();
+        assert_eq!(config.gestures[0].actions.len(), 1);
+        assert_eq!(config.gestures[1].name, "forward");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,156 @@
+use crate::{Point, TemplateMatcher};
+
+//Helpers to turn recorded sample strokes into gesture points
+
+//Points per stroke, when samples are compared with each other
+const COMPARED_POINTS: usize = 32;
+//Smallest suggested tolerance, since the samples of a few tries rarely cover all variations
+const MIN_TOLERANCE: f32 = 10.0;
+//Headroom on top of the largest deviation between the samples
+const TOLERANCE_FACTOR: f32 = 1.25;
+
+//Ramer–Douglas–Peucker: drops points, which are closer than epsilon to the line between their neighbours
+pub fn simplify(points: &[Point], epsilon: f32) -> Vec<Point> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+    let mut ranges = vec![(0, points.len() - 1)];
+    while let Some((start, end)) = ranges.pop() {
+        let farthest = (start + 1..end)
+            .map(|i| (i, line_distance(&points[i], &points[start], &points[end])))
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+        if let Some((i, distance)) = farthest {
+            if distance > epsilon {
+                keep[i] = true;
+                ranges.push((start, i));
+                ranges.push((i, end));
+            }
+        }
+    }
+    points
+        .iter()
+        .zip(keep)
+        .filter(|(_, keep)| *keep)
+        .map(|(point, _)| point.clone())
+        .collect()
+}
+
+//Distance of the point to the line through start and end, or to start if both are the same
+fn line_distance(point: &Point, start: &Point, end: &Point) -> f32 {
+    let length = start.distance(end);
+    if length == 0.0 {
+        return point.distance(start);
+    }
+    let (dx, dy) = ((end.x - start.x) as f32, (end.y - start.y) as f32);
+    (dy * (point.x - start.x) as f32 - dx * (point.y - start.y) as f32).abs() / length
+}
+
+//Moves all strokes, so that the first point of the first stroke is at (0 0)
+pub fn normalize(strokes: &[Vec<Point>]) -> Vec<Vec<Point>> {
+    let origin = match strokes.first().and_then(|stroke| stroke.first()) {
+        Some(origin) => origin.clone(),
+        None => return Vec::new(),
+    };
+    strokes
+        .iter()
+        .map(|stroke| {
+            stroke
+                .iter()
+                .map(|point| Point::new(point.x - origin.x, point.y - origin.y))
+                .collect()
+        })
+        .collect()
+}
+
+//Largest per axis distance between the normalized samples, after resampling their strokes
+//None if the samples have a different number of strokes
+pub fn deviation(a: &[Vec<Point>], b: &[Vec<Point>]) -> Option<f32> {
+    if a.len() != b.len() {
+        return None;
+    }
+    let (a, b) = (normalize(a), normalize(b));
+    let mut deviation: f32 = 0.0;
+    for (stroke_a, stroke_b) in a.iter().zip(&b) {
+        let resampled_a = TemplateMatcher::resample(stroke_a, COMPARED_POINTS);
+        let resampled_b = TemplateMatcher::resample(stroke_b, COMPARED_POINTS);
+        for ((xa, ya), (xb, yb)) in resampled_a.iter().zip(&resampled_b) {
+            deviation = deviation.max((xa - xb).abs()).max((ya - yb).abs());
+        }
+    }
+    Some(deviation)
+}
+
+//Index of the sample closest to all others, which serves as the gesture
+//and the tolerance, with which the others would still match it
+pub fn pick_template(samples: &[Vec<Vec<Point>>]) -> Option<(usize, Option<f32>)> {
+    if samples.len() < 2 {
+        return samples.first().map(|_| (0, None));
+    }
+    (0..samples.len())
+        .filter_map(|i| {
+            let deviations: Option<Vec<f32>> = samples
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, sample)| deviation(&samples[i], sample))
+                .collect();
+            Some((i, deviations?.into_iter().fold(0.0, f32::max)))
+        })
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+        .map(|(i, deviation)| {
+            let tolerance = (deviation * TOLERANCE_FACTOR).ceil().max(MIN_TOLERANCE);
+            (i, Some(tolerance))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{coordinates, sample_points};
+
+    #[test]
+    fn simplify_stroke() {
+        let points = sample_points(&[
+            (10, 10),
+            (30, 11),
+            (50, 9),
+            (70, 10),
+            (70, 30),
+            (71, 50),
+            (70, 70),
+        ]);
+        assert_eq!(
+            coordinates(&simplify(&points, 4.0)),
+            vec![(10, 10), (70, 10), (70, 70)]
+        );
+        assert_eq!(simplify(&points, 0.25).len(), points.len());
+        let normalized = normalize(&[points, sample_points(&[(20, 40), (30, 40)])]);
+        assert_eq!(coordinates(&normalized[0][..1]), vec![(0, 0)]);
+        assert_eq!(coordinates(&normalized[1]), vec![(10, 30), (20, 30)]);
+    }
+
+    #[test]
+    fn suggested_tolerance() {
+        let samples = vec![
+            vec![sample_points(&[(100, 100), (200, 100)])],
+            vec![sample_points(&[(0, 0), (100, 12)])],
+            vec![sample_points(&[(50, 50), (150, 45)])],
+        ];
+        //The first sample lies between the others
+        assert_eq!(pick_template(&samples), Some((0, Some(15.0))));
+        let wide = vec![
+            samples[0].clone(),
+            vec![sample_points(&[(0, 0), (100, 40)])],
+        ];
+        assert_eq!(pick_template(&wide), Some((0, Some(50.0))));
+        assert_eq!(pick_template(&samples[..1]), Some((0, None)));
+        let strokes = vec![
+            samples[0].clone(),
+            vec![sample_points(&[(0, 0)]), sample_points(&[(0, 0)])],
+        ];
+        assert!(pick_template(&strokes).is_none());
+    }
+}
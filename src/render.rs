@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+use xcb::render::{Color, Pointfix};
+use xcb::Connection;
+
+//Anti-aliased drawing through the RENDER extension
+//Shapes are made of triangles, which are added up in an opaque coverage mask,
+//through which the color is composited at once, so overlapping shapes do not darken translucent colors
+
+//Stays below the maximum request length of the core protocol
+const TRIANGLES_PER_REQUEST: usize = 4096;
+//Circles get a segment per pixel of their radius, within these bounds
+const MIN_CIRCLE_SEGMENTS: usize = 8;
+const MAX_CIRCLE_SEGMENTS: usize = 48;
+
+pub type Triangle = [(f32, f32); 3];
+//Position and size of a rectangle in pixels
+pub type Area = (i16, i16, u16, u16);
+
+pub struct Render {
+    //Format of the visual of the drawables
+    format: u32,
+    //8 bit alpha, in which the triangles are rasterized
+    mask_format: u32,
+    //Pixmap and picture of the coverage mask
+    mask: (u32, u32),
+    size: (u16, u16),
+    //Picture of every drawn on drawable
+    pictures: HashMap<u32, u32>,
+    //Solid fill picture of every used pixel value
+    fills: HashMap<u32, u32>,
+}
+
+impl Render {
+    //Solid fills need RENDER 0.10, None if it is missing or the visual has no format
+    //The coverage mask is created on the screen of the drawable and has the size of the drawables
+    pub fn new(conn: &Connection, visual: u32, drawable: u32, size: (u16, u16)) -> Option<Render> {
+        //Requests of a missing extension would close the connection
+        if !conn
+            .get_extension_data(xcb::render::id())
+            .map(|data| data.present())
+            .unwrap_or(false)
+        {
+            return None;
+        }
+        let version = xcb::render::query_version(conn, 0, 11).get_reply().ok()?;
+        if (version.major_version(), version.minor_version()) < (0, 10) {
+            return None;
+        }
+        let formats = xcb::render::query_pict_formats(conn).get_reply().ok()?;
+        let (format, mask_format) = find_formats(&formats, visual)?;
+        let pixmap = conn.generate_id();
+        xcb::create_pixmap(conn, 8, pixmap, drawable, size.0, size.1);
+        let picture = conn.generate_id();
+        xcb::render::create_picture(conn, picture, pixmap, mask_format, &[]);
+        let mut render = Render {
+            format,
+            mask_format,
+            mask: (pixmap, picture),
+            size,
+            pictures: HashMap::new(),
+            fills: HashMap::new(),
+        };
+        render.clear_coverage(conn);
+        Some(render)
+    }
+
+    fn picture(&mut self, conn: &Connection, drawable: u32) -> u32 {
+        let format = self.format;
+        *self.pictures.entry(drawable).or_insert_with(|| {
+            let picture = conn.generate_id();
+            xcb::render::create_picture(conn, picture, drawable, format, &[]);
+            picture
+        })
+    }
+
+    //The pixel is a premultiplied ARGB value, like the colors of the config
+    fn fill(&mut self, conn: &Connection, pixel: u32) -> u32 {
+        *self.fills.entry(pixel).or_insert_with(|| {
+            let picture = conn.generate_id();
+            xcb::render::create_solid_fill(conn, picture, color(pixel));
+            picture
+        })
+    }
+
+    //Adds the triangles to the coverage mask, where they overlap it stays opaque
+    pub fn add_coverage(&mut self, conn: &Connection, triangles: &[Triangle]) {
+        let source = self.fill(conn, 0xffffffff);
+        let point = |(x, y): (f32, f32)| Pointfix::new(fixed(x), fixed(y));
+        for chunk in triangles.chunks(TRIANGLES_PER_REQUEST) {
+            let chunk: Vec<xcb::render::Triangle> = chunk
+                .iter()
+                .map(|[p1, p2, p3]| xcb::render::Triangle::new(point(*p1), point(*p2), point(*p3)))
+                .collect();
+            xcb::render::triangles(
+                conn,
+                xcb::render::PICT_OP_OVER as u8,
+                source,
+                self.mask.1,
+                self.mask_format,
+                0,
+                0,
+                &chunk,
+            );
+        }
+    }
+
+    pub fn clear_coverage(&mut self, conn: &Connection) {
+        xcb::render::fill_rectangles(
+            conn,
+            xcb::render::PICT_OP_SRC as u8,
+            self.mask.1,
+            color(0),
+            &[xcb::Rectangle::new(0, 0, self.size.0, self.size.1)],
+        );
+    }
+
+    //Composites the pixel through the coverage mask within the area of the drawable
+    pub fn composite(&mut self, conn: &Connection, drawable: u32, pixel: u32, area: Area) {
+        let destination = self.picture(conn, drawable);
+        let source = self.fill(conn, pixel);
+        let (x, y, width, height) = area;
+        xcb::render::composite(
+            conn,
+            xcb::render::PICT_OP_OVER as u8,
+            source,
+            self.mask.1,
+            destination,
+            0,
+            0,
+            x,
+            y,
+            x,
+            y,
+            width,
+            height,
+        );
+    }
+
+    pub fn free(&mut self, conn: &Connection) {
+        let pictures = self.pictures.values().chain(self.fills.values());
+        for picture in pictures.chain(std::iter::once(&self.mask.1)) {
+            xcb::render::free_picture(conn, *picture);
+        }
+        xcb::free_pixmap(conn, self.mask.0);
+        self.pictures.clear();
+        self.fills.clear();
+    }
+}
+
+//The pixels covered by the triangles, within the size
+pub fn bounds(triangles: &[Triangle], size: (u16, u16)) -> Option<Area> {
+    if triangles.is_empty() {
+        return None;
+    }
+    let points = triangles.iter().flatten();
+    let (min_x, min_y, max_x, max_y) = points.fold(
+        (f32::MAX, f32::MAX, f32::MIN, f32::MIN),
+        |(min_x, min_y, max_x, max_y), (x, y)| {
+            (min_x.min(*x), min_y.min(*y), max_x.max(*x), max_y.max(*y))
+        },
+    );
+    let min = |value: f32| value.floor().max(0.0) as i32;
+    let max = |value: f32, limit: u16| (value.ceil() as i32).min(limit as i32);
+    let (x, y) = (min(min_x), min(min_y));
+    let (width, height) = (max(max_x, size.0) - x, max(max_y, size.1) - y);
+    if width <= 0 || height <= 0 {
+        return None;
+    }
+    Some((x as i16, y as i16, width as u16, height as u16))
+}
+
+//16.16 fixed point
+fn fixed(value: f32) -> i32 {
+    (value * 65536.0) as i32
+}
+
+//16 bit channels of the premultiplied ARGB pixel
+fn color(pixel: u32) -> Color {
+    let channel = |shift: u32| ((pixel >> shift) & 0xff) as u16 * 257;
+    Color::new(channel(16), channel(8), channel(0), channel(24))
+}
+
+//The format of the visual and an 8 bit alpha format
+fn find_formats(reply: &xcb::render::QueryPictFormatsReply, visual: u32) -> Option<(u32, u32)> {
+    let mask_format = reply
+        .formats()
+        .find(|format| {
+            format.type_() == xcb::render::PICT_TYPE_DIRECT as u8
+                && format.depth() == 8
+                && format.direct().alpha_mask() == 0xff
+        })
+        .map(|format| format.id())?;
+    reply
+        .screens()
+        .flat_map(|screen| screen.depths())
+        .flat_map(|depth| depth.visuals())
+        .find(|pict_visual| pict_visual.visual() == visual)
+        .map(|pict_visual| (pict_visual.format(), mask_format))
+}
+
+pub fn circle(center: (f32, f32), radius: f32) -> Vec<Triangle> {
+    let count = (radius.ceil() as usize).clamp(MIN_CIRCLE_SEGMENTS, MAX_CIRCLE_SEGMENTS);
+    let point = |i: usize| {
+        let angle = i as f32 * std::f32::consts::TAU / count as f32;
+        (
+            center.0 + radius * angle.cos(),
+            center.1 + radius * angle.sin(),
+        )
+    };
+    (0..count)
+        .map(|i| [center, point(i), point(i + 1)])
+        .collect()
+}
+
+//The body of a line between two points, without caps
+pub fn rectangle(from: (f32, f32), to: (f32, f32), radius: f32) -> Vec<Triangle> {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let length = (dx * dx + dy * dy).sqrt();
+    if length == 0.0 {
+        return Vec::new();
+    }
+    //Perpendicular to the line, with the length of the radius
+    let (nx, ny) = (-dy / length * radius, dx / length * radius);
+    let corners = [
+        (from.0 + nx, from.1 + ny),
+        (to.0 + nx, to.1 + ny),
+        (to.0 - nx, to.1 - ny),
+        (from.0 - nx, from.1 - ny),
+    ];
+    vec![
+        [corners[0], corners[1], corners[2]],
+        [corners[0], corners[2], corners[3]],
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shapes() {
+        let circle = circle((10.0, 10.0), 20.0);
+        assert_eq!(circle.len(), 20);
+        for [_, (x, y), _] in &circle {
+            let distance = ((x - 10.0).powi(2) + (y - 10.0).powi(2)).sqrt();
+            assert!((distance - 20.0).abs() < 0.001);
+        }
+        let rectangle = rectangle((0.0, 0.0), (100.0, 0.0), 5.0);
+        assert_eq!(rectangle[0], [(0.0, 5.0), (100.0, 5.0), (100.0, -5.0)]);
+        assert_eq!(rectangle[1], [(0.0, 5.0), (100.0, -5.0), (0.0, -5.0)]);
+        assert!(super::rectangle((1.0, 1.0), (1.0, 1.0), 5.0).is_empty());
+        assert_eq!(bounds(&rectangle, (50, 50)), Some((0, 0, 50, 5)));
+        assert_eq!(bounds(&circle, (100, 100)), Some((0, 0, 30, 30)));
+        assert_eq!(bounds(&[], (100, 100)), None);
+        assert_eq!(fixed(1.5), 0x18000);
+    }
+}
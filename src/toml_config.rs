@@ -0,0 +1,667 @@
+use crate::config::{self, Config, Diagnostic, Loader, Machine};
+use crate::{Action, Constraint, Gesture, GestureAttributes, Matcher, Point, Preprocessing};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+//Schema of traps.toml, which loads into the same Config as trapsrc
+//Omitted settings keep the defaults of trapsrc
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TomlConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fg: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bg: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub r: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub global_min_score: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub global_preprocess: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stroke_pause: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fast_trigger: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub overlay_monitor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monitor_areas: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trail_antialias: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trail_fade: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feedback_time: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feedback_font: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feedback_color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feedback_shape: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub watch_config: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trigger_button: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    //Key chords and their actions, like key.ctrl+r = reload in trapsrc, in the order of the file
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub key: IndexMap<String, String>,
+    #[serde(default, rename = "gesture", skip_serializing_if = "Vec::is_empty")]
+    pub gestures: Vec<TomlGesture>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TomlGesture {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matcher: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_relative: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tolerance: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_score: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preprocess: Option<String>,
+    //Only loaded on the host or with the screen resolution, like [host = ...] sections of trapsrc
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<String>,
+    pub points: TomlPoints,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<TomlAction>,
+    #[serde(default, skip_serializing_if = "TomlConstraints::is_empty")]
+    pub constraints: TomlConstraints,
+}
+
+//The point syntax of trapsrc with primitives and strokes separated by |,
+//a list of [x, y] pairs or a list of strokes
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum TomlPoints {
+    Text(String),
+    Stroke(Vec<(i16, i16)>),
+    Strokes(Vec<Vec<(i16, i16)>>),
+}
+
+impl Default for TomlPoints {
+    fn default() -> TomlPoints {
+        TomlPoints::Text(String::new())
+    }
+}
+
+//Exactly one of the fields is set, e.g. { key = "ctrl+w" } or { exec = ["firefox", "--new-window"] }
+#[derive(Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TomlAction {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exec: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shell: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub click: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub builtin: Option<String>,
+}
+
+//Values use the syntax of trapsrc
+#[derive(Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TomlConstraints {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_area: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_area: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_duration: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_duration: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_length: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_deviation: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_edge: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monitor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window_class: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window_name: Option<String>,
+}
+
+impl TomlConfig {
+    pub fn parse_into(loader: &mut Loader, content: &str, file: &Path) {
+        match toml::from_str::<TomlConfig>(content) {
+            Ok(toml_config) => toml_config.apply(loader, file),
+            Err(error) => {
+                let (line, column) = error
+                    .line_col()
+                    .map_or((0, 0), |(line, column)| (line + 1, column + 1));
+                loader
+                    .diagnostics
+                    .push(Diagnostic::error(file, line, column, error.to_string()));
+            }
+        }
+    }
+
+    //Includes are loaded first, so the settings and gestures of the file override theirs
+    //Positions within the file are not known anymore, so errors name the setting or gesture
+    fn apply(&self, loader: &mut Loader, file: &Path) {
+        let mut errors = Vec::new();
+        for pattern in &self.include {
+            if let Err(message) = loader.include(pattern, file) {
+                errors.push(format!("include: {}", message));
+            }
+        }
+        let config = &mut loader.config;
+        if let Some(color) = &self.fg {
+            match config::parse_hex(color) {
+                Some(color) => config.fg = color,
+                None => errors.push(format!(
+                    "fg: {}",
+                    config::expected("a color like #rrggbbaa", color)
+                )),
+            }
+        }
+        if let Some(color) = &self.bg {
+            match config::parse_hex(color) {
+                Some(color) => config.bg = color,
+                None => errors.push(format!(
+                    "bg: {}",
+                    config::expected("a color like #rrggbbaa", color)
+                )),
+            }
+        }
+        if let Some(color) = &self.feedback_color {
+            match config::parse_hex(color) {
+                Some(color) => config.feedback_color = color,
+                None => errors.push(format!(
+                    "feedback_color: {}",
+                    config::expected("a color like #rrggbbaa", color)
+                )),
+            }
+        }
+        if let Some(monitor) = &self.overlay_monitor {
+            match config::OverlayMonitor::from_name(monitor) {
+                Some(monitor) => config.overlay_monitor = monitor,
+                None => errors.push(format!(
+                    "overlay_monitor: {}",
+                    config::expected("all or pointer", monitor)
+                )),
+            }
+        }
+        if let Some(trigger) = &self.trigger_button {
+            match Config::parse_trigger(trigger) {
+                Some(trigger) => config.trigger = Some(trigger),
+                None => errors.push(format!(
+                    "trigger_button: {}",
+                    config::expected("modifiers and a button like super+3", trigger)
+                )),
+            }
+        }
+        if let Some(preprocess) = &self.global_preprocess {
+            match Preprocessing::parse(preprocess) {
+                Ok(preprocessing) => config.preprocessing = preprocessing,
+                Err(message) => errors.push(format!("global_preprocess: {}", message)),
+            }
+        }
+        config.r = self.r.unwrap_or(config.r);
+        config.min_score = self.global_min_score.unwrap_or(config.min_score);
+        config.stroke_pause = self.stroke_pause.unwrap_or(config.stroke_pause);
+        config.fast_trigger = self.fast_trigger.unwrap_or(config.fast_trigger);
+        config.monitor_areas = self.monitor_areas.unwrap_or(config.monitor_areas);
+        config.trail_antialias = self.trail_antialias.unwrap_or(config.trail_antialias);
+        config.trail_fade = self.trail_fade.unwrap_or(config.trail_fade);
+        config.feedback_time = self.feedback_time.unwrap_or(config.feedback_time);
+        if let Some(font) = &self.feedback_font {
+            config.feedback_font = font.clone();
+        }
+        config.feedback_shape = self.feedback_shape.unwrap_or(config.feedback_shape);
+        config.watch = self.watch_config.unwrap_or(config.watch);
+        for (chord, value) in &self.key {
+            match Config::parse_key_binding(chord, value) {
+                Ok((keysym, modifiers, action)) => config.bind(keysym, modifiers, action),
+                Err(message) => errors.push(format!("key.{}: {}", chord, message)),
+            }
+        }
+        for gesture in &self.gestures {
+            let result = gesture
+                .is_for(&loader.machine)
+                .and_then(|active| Ok((active, gesture.to_gesture()?)));
+            match result {
+                Ok((true, parsed)) => loader.add_gesture(parsed, file, 0, 0),
+                Ok((false, _)) => {}
+                Err(message) => errors.push(format!("gesture '{}': {}", gesture.name, message)),
+            }
+        }
+        for message in errors {
+            loader
+                .diagnostics
+                .push(Diagnostic::error(file, 0, 0, message));
+        }
+    }
+
+    //Converts trapsrc, keeping the point syntax and the values of constraints as they are
+    pub fn from_trapsrc(
+        content: &str,
+        file: &Path,
+    ) -> Result<(TomlConfig, Vec<Diagnostic>), Vec<Diagnostic>> {
+        let (_, mut diagnostics) = Config::parse(content, file)?;
+        let mut toml_config = TomlConfig::default();
+        let mut gesture = TomlGesture::default();
+        let mut scope: Option<(String, String)> = None;
+        let mut condition: Option<(String, String)> = None;
+        for (index, line) in content.lines().enumerate() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            if trimmed.starts_with('[') && trimmed.ends_with(']') {
+                let section = trimmed[1..trimmed.len() - 1]
+                    .split_once('=')
+                    .map(|(key, value)| (key.trim().to_owned(), value.trim().to_owned()));
+                match &section {
+                    Some((key, _)) if key == "host" || key == "resolution" => condition = section,
+                    Some(_) => scope = section,
+                    None => {
+                        scope = None;
+                        condition = None;
+                    }
+                }
+                continue;
+            }
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => continue,
+            };
+            let is_setting = !config::GESTURE_KEYS.contains(&key) && key != "points";
+            if is_setting && condition.is_some() {
+                diagnostics.push(Diagnostic::warning(
+                    file,
+                    index + 1,
+                    1,
+                    format!(
+                        "'{}' of a [host = ...] or [resolution = ...] section is not converted",
+                        key
+                    ),
+                ));
+                continue;
+            }
+            let constraints = &mut gesture.constraints;
+            match key {
+                "name" => gesture.name = value.to_owned(),
+                "is_relative" => gesture.is_relative = value.parse().ok(),
+                "action" => gesture.actions.push(TomlAction::from_trapsrc(value)),
+                "tolerance" => gesture.tolerance = value.parse().ok(),
+                "matcher" => gesture.matcher = Some(value.to_owned()),
+                "min_score" => gesture.min_score = value.parse().ok(),
+                "preprocess" => gesture.preprocess = Some(value.to_owned()),
+                "start_area" => constraints.start_area = Some(value.to_owned()),
+                "stop_area" => constraints.stop_area = Some(value.to_owned()),
+                "min_duration" => constraints.min_duration = value.parse().ok(),
+                "max_duration" => constraints.max_duration = value.parse().ok(),
+                "min_length" => constraints.min_length = value.parse().ok(),
+                "max_deviation" => constraints.max_deviation = value.parse().ok(),
+                "start_edge" => constraints.start_edge = Some(value.to_owned()),
+                "monitor" => constraints.monitor = Some(value.to_owned()),
+                "window_class" => constraints.window_class = Some(value.to_owned()),
+                "window_name" => constraints.window_name = Some(value.to_owned()),
+                "points" => {
+                    gesture.points = TomlPoints::Text(value.to_owned());
+                    if let Some((key, value)) = &scope {
+                        let constraint = match key.as_str() {
+                            "window_class" => &mut constraints.window_class,
+                            _ => &mut constraints.window_name,
+                        };
+                        if constraint.is_some() {
+                            diagnostics.push(Diagnostic::warning(
+                                file,
+                                index + 1,
+                                1,
+                                format!(
+                                    "the {} of the section is replaced by the one of the gesture",
+                                    key
+                                ),
+                            ));
+                        } else {
+                            *constraint = Some(value.clone());
+                        }
+                    }
+                    match &condition {
+                        Some((key, value)) if key == "host" => gesture.host = Some(value.clone()),
+                        Some((_, value)) => gesture.resolution = Some(value.clone()),
+                        None => {}
+                    }
+                    toml_config.gestures.push(std::mem::take(&mut gesture));
+                }
+                //Includes of TOML are loaded before the rest of the file
+                "include" => {
+                    if !toml_config.gestures.is_empty() {
+                        diagnostics.push(Diagnostic::warning(
+                            file,
+                            index + 1,
+                            1,
+                            "the include is loaded before the gestures of the file".to_owned(),
+                        ));
+                    }
+                    toml_config.include.push(value.to_owned());
+                }
+                "bg" => toml_config.bg = Some(value.to_owned()),
+                "fg" => toml_config.fg = Some(value.to_owned()),
+                "r" => toml_config.r = value.parse().ok(),
+                "global_min_score" => toml_config.global_min_score = value.parse().ok(),
+                "global_preprocess" => toml_config.global_preprocess = Some(value.to_owned()),
+                "stroke_pause" => toml_config.stroke_pause = value.parse().ok(),
+                "fast_trigger" => toml_config.fast_trigger = value.parse().ok(),
+                "overlay_monitor" => toml_config.overlay_monitor = Some(value.to_owned()),
+                "monitor_areas" => toml_config.monitor_areas = value.parse().ok(),
+                "trail_antialias" => toml_config.trail_antialias = value.parse().ok(),
+                "trail_fade" => toml_config.trail_fade = value.parse().ok(),
+                "feedback_time" => toml_config.feedback_time = value.parse().ok(),
+                "feedback_font" => toml_config.feedback_font = Some(value.to_owned()),
+                "feedback_color" => toml_config.feedback_color = Some(value.to_owned()),
+                "feedback_shape" => toml_config.feedback_shape = value.parse().ok(),
+                "watch_config" => toml_config.watch_config = value.parse().ok(),
+                "trigger_button" => toml_config.trigger_button = Some(value.to_owned()),
+                _ if key.starts_with("key.") => {
+                    toml_config
+                        .key
+                        .insert(key[4..].to_owned(), value.to_owned());
+                }
+                _ => {}
+            }
+        }
+        Ok((toml_config, diagnostics))
+    }
+}
+
+impl TomlGesture {
+    fn is_for(&self, machine: &Machine) -> Result<bool, String> {
+        let conditions = [("host", &self.host), ("resolution", &self.resolution)];
+        for (key, value) in conditions.iter() {
+            if let Some(Some(matches)) = value.as_ref().map(|value| machine.matches(key, value)) {
+                if !matches? {
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    fn to_gesture(&self) -> Result<Gesture, String> {
+        let mut attributes = GestureAttributes {
+            name: &self.name,
+            ..Default::default()
+        };
+        attributes.is_relative = self.is_relative.unwrap_or(attributes.is_relative);
+        attributes.tolerance = self.tolerance.unwrap_or(attributes.tolerance);
+        attributes.min_score = self.min_score;
+        if let Some(preprocess) = &self.preprocess {
+            attributes.preprocessing = Some(Preprocessing::parse(preprocess)?);
+        }
+        if let Some(matcher) = &self.matcher {
+            attributes.matcher = Matcher::from_name(matcher)
+                .ok_or_else(|| config::expected("delta, template or rotating_template", matcher))?;
+        }
+        for action in &self.actions {
+            attributes.actions.push(action.to_action()?);
+        }
+        attributes.constraints = self.constraints.to_constraints()?;
+        let strokes = match &self.points {
+            TomlPoints::Text(text) => Config::parse_strokes(text)
+                .map_err(|(offset, message)| format!("points at {}: {}", offset + 1, message))?,
+            TomlPoints::Stroke(stroke) => vec![Self::to_points(stroke)],
+            TomlPoints::Strokes(strokes) => strokes
+                .iter()
+                .map(|stroke| Self::to_points(stroke))
+                .collect(),
+        };
+        if strokes.iter().any(|stroke| stroke.is_empty()) {
+            return Err("a stroke needs points".to_owned());
+        }
+        let mut gesture = Gesture::new(&attributes);
+        for stroke in strokes {
+            gesture.add_stroke(stroke);
+        }
+        Ok(gesture)
+    }
+
+    fn to_points(stroke: &[(i16, i16)]) -> Vec<Point> {
+        stroke.iter().map(|(x, y)| Point::new(*x, *y)).collect()
+    }
+}
+
+impl TomlAction {
+    fn to_action(&self) -> Result<Action, String> {
+        match self {
+            TomlAction {
+                exec: Some(arguments),
+                shell: None,
+                key: None,
+                click: None,
+                builtin: None,
+            } => {
+                if arguments.is_empty() {
+                    Err("exec needs a program".to_owned())
+                } else {
+                    Ok(Action::Exec(arguments.clone()))
+                }
+            }
+            TomlAction {
+                exec: None,
+                shell: Some(command),
+                key: None,
+                click: None,
+                builtin: None,
+            } => Ok(Action::Shell(command.clone())),
+            TomlAction {
+                exec: None,
+                shell: None,
+                key: Some(chord),
+                click: None,
+                builtin: None,
+            } => Action::parse(&format!("key: {}", chord)),
+            TomlAction {
+                exec: None,
+                shell: None,
+                key: None,
+                click: Some(button),
+                builtin: None,
+            } => Action::parse(&format!("click: {}", button)),
+            TomlAction {
+                exec: None,
+                shell: None,
+                key: None,
+                click: None,
+                builtin: Some(name),
+            } => Action::parse(&format!("builtin: {}", name)),
+            _ => {
+                Err("an action needs exactly one of exec, shell, key, click or builtin".to_owned())
+            }
+        }
+    }
+
+    //The value is valid, since the config was parsed before
+    pub fn from_trapsrc(value: &str) -> TomlAction {
+        let argument = value
+            .split_once(':')
+            .map_or("", |(_, argument)| argument.trim());
+        match Action::parse(value) {
+            Ok(Action::Exec(arguments)) => TomlAction {
+                exec: Some(arguments),
+                ..Default::default()
+            },
+            Ok(Action::Key(_)) => TomlAction {
+                key: Some(argument.to_owned()),
+                ..Default::default()
+            },
+            Ok(Action::Click(button)) => TomlAction {
+                click: Some(button),
+                ..Default::default()
+            },
+            Ok(Action::Builtin(_)) => TomlAction {
+                builtin: Some(argument.to_owned()),
+                ..Default::default()
+            },
+            Ok(Action::Shell(command)) => TomlAction {
+                shell: Some(command),
+                ..Default::default()
+            },
+            Err(_) => TomlAction {
+                shell: Some(value.to_owned()),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl TomlConstraints {
+    fn is_empty(&self) -> bool {
+        *self == TomlConstraints::default()
+    }
+
+    fn to_constraints(&self) -> Result<Vec<Constraint>, String> {
+        let mut constraints = Vec::new();
+        if let Some(area) = &self.start_area {
+            constraints.push(Constraint::StartArea(Config::parse_area(area)?));
+        }
+        if let Some(area) = &self.stop_area {
+            constraints.push(Constraint::StopArea(Config::parse_area(area)?));
+        }
+        constraints.extend(self.min_duration.map(Constraint::MinDuration));
+        constraints.extend(self.max_duration.map(Constraint::MaxDuration));
+        constraints.extend(self.min_length.map(Constraint::MinLength));
+        constraints.extend(self.max_deviation.map(Constraint::MaxDeviation));
+        if let Some(edge) = &self.start_edge {
+            let (edge, margin) = Config::parse_edge(edge).ok_or_else(|| {
+                config::expected("an edge or corner like top or top_left, and a margin", edge)
+            })?;
+            constraints.push(Constraint::StartEdge(edge, margin));
+        }
+        constraints.extend(self.monitor.clone().map(Constraint::Monitor));
+        if let Some(class) = &self.window_class {
+            constraints.push(Config::parse_window_constraint("window_class", class)?);
+        }
+        if let Some(name) = &self.window_name {
+            constraints.push(Config::parse_window_constraint("window_name", name)?);
+        }
+        Ok(constraints)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRAPSRC: &str = "fg = #ff0000ff\n\
+        feedback_time = 800\n\
+        trigger_button = super+3\n\
+        name = close\n\
+        action = key: ctrl+w\n\
+        action = exec: notify-send 'Tab closed'\n\
+        start_area = smaller (50% 100%)\n\
+        points = (0 0), (0 100) | Line(0 0 100 0)\n\
+        [window_class = Alacritty]\n\
+        name = reload\n\
+        action = builtin: reload\n\
+        action = click: right\n\
+        key.ctrl+r = reload\n\
+        key.alt+h = hide\n\
+        points = Circle(0 0 50)\n";
+
+    fn parse(content: &str) -> Result<(Config, Vec<Diagnostic>), Vec<Diagnostic>> {
+        let mut loader = Loader::new(Machine::default());
+        TomlConfig::parse_into(&mut loader, content, Path::new("traps.toml"));
+        loader.finish()
+    }
+
+    fn names_and_actions(config: &Config) -> Vec<(String, Vec<Action>, usize, bool)> {
+        config
+            .gestures
+            .iter()
+            .map(|gesture| {
+                (
+                    gesture.name.clone(),
+                    gesture.actions.clone(),
+                    gesture.stroke_count(),
+                    gesture.is_window_specific(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn toml() {
+        let (config, _) = parse(
+            "r = 4\n\
+             [[gesture]]\n\
+             name = \"back\"\n\
+             matcher = \"template\"\n\
+             points = [[0, 0], [-100, 0]]\n\
+             actions = [{ key = \"alt+left\" }, { exec = [\"notify-send\", \"Back\"] }]\n\
+             constraints = { start_edge = \"left 10\", window_class = \"firefox\" }\n\
+             [[gesture]]\n\
+             name = \"two strokes\"\n\
+             points = [[[0, 0], [0, 100]], [[0, 0], [100, 0]]]\n",
+        )
+        .unwrap();
+        assert_eq!(config.r, 4);
+        assert_eq!(config.gestures[0].matcher, Matcher::Template);
+        assert_eq!(
+            names_and_actions(&config),
+            vec![
+                (
+                    "back".to_owned(),
+                    vec![
+                        Action::Key(vec![crate::keysym::ALT, 0xff51]),
+                        Action::Exec(vec!["notify-send".to_owned(), "Back".to_owned()])
+                    ],
+                    1,
+                    true
+                ),
+                ("two strokes".to_owned(), Vec::new(), 2, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn toml_errors() {
+        let errors = parse("[[gesture]]\nname = 5").err().unwrap();
+        assert_eq!(errors[0].line, 2);
+        let errors = parse(
+            "[[gesture]]\nname = \"a\"\npoints = \"(0 0), (1 x)\"\nactions = [{ key = \"a\", shell = \"b\" }]",
+        )
+        .err().unwrap();
+        assert_eq!(
+            errors[0].to_string(),
+            "traps.toml: error: gesture 'a': an action needs exactly one of exec, shell, key, click or builtin"
+        );
+    }
+
+    #[test]
+    fn convert() {
+        let file = Path::new("trapsrc");
+        let (toml_config, _) = TomlConfig::from_trapsrc(TRAPSRC, file).unwrap();
+        let chords: Vec<&str> = toml_config.key.keys().map(String::as_str).collect();
+        assert_eq!(chords, vec!["ctrl+r", "alt+h"]);
+        let text = toml::to_string(&toml_config).unwrap();
+        assert!(text.find("ctrl+r") < text.find("alt+h"));
+        let (converted, _) = parse(&text).unwrap();
+        let (original, _) = Config::parse(TRAPSRC, file).unwrap();
+        assert_eq!(names_and_actions(&converted), names_and_actions(&original));
+        assert_eq!(converted.fg, original.fg);
+        assert_eq!(converted.feedback_time, 800);
+        assert_eq!(converted.key_bindings, original.key_bindings);
+        assert_eq!(converted.key_bindings.len(), 4);
+        assert_eq!(converted.trigger, original.trigger);
+        assert!(TomlConfig::from_trapsrc("r = wide", file).is_err());
+    }
+}
@@ -0,0 +1,206 @@
+use std::time::{Duration, Instant};
+
+//The trail of the strokes on the overlay, which can fade out over time
+//Drawing is left to a Renderer, e.g. the X11 overlay
+
+//Time between redraws of a fading trail
+const FADE_INTERVAL: Duration = Duration::from_millis(40);
+//Steps of transparency of a fading trail, which limits the number of colors
+const FADE_LEVELS: f32 = 16.0;
+
+//A line from the first to the second point, or a dot at the second point
+pub type TrailSegment = (Option<(i16, i16)>, (i16, i16));
+
+pub trait Renderer {
+    //Draws lines with round caps from the first to the second point, or a dot without a first point
+    //Colors are premultiplied ARGB values
+    fn draw_segments(&mut self, color: u32, segments: &[TrailSegment]);
+    //Replaces everything drawn so far with the segments of each color
+    fn redraw(&mut self, layers: &[(u32, Vec<TrailSegment>)]);
+    fn clear(&mut self);
+    //Sends what was drawn, if the renderer buffers it
+    fn flush(&mut self);
+}
+
+pub struct Trail<R: Renderer> {
+    pub renderer: R,
+    pub color: u32,
+    //Time, after which points have faded out, zero keeps the whole trail
+    pub fade: Duration,
+    //Points of the strokes of the current sequence and when they were drawn
+    strokes: Vec<Vec<(i16, i16, Instant)>>,
+    //Set while a fading trail has visible points
+    deadline: Option<Instant>,
+}
+
+impl<R: Renderer> Trail<R> {
+    pub fn new(renderer: R, color: u32, fade: Duration) -> Trail<R> {
+        Trail {
+            renderer,
+            color,
+            fade,
+            strokes: Vec::new(),
+            deadline: None,
+        }
+    }
+
+    pub fn begin_stroke(&mut self) {
+        self.strokes.push(Vec::new());
+    }
+
+    //Adds the point to the last stroke and connects it to the previous point
+    pub fn add_point(&mut self, x: i16, y: i16) {
+        let now = Instant::now();
+        if self.strokes.is_empty() {
+            self.strokes.push(Vec::new());
+        }
+        let stroke = self.strokes.last_mut().unwrap();
+        let previous = stroke.last().map(|(x, y, _)| (*x, *y));
+        stroke.push((x, y, now));
+        //Older parts of a fading trail have other colors, so it is drawn as a whole
+        if !self.fade.is_zero() {
+            self.fade();
+            return;
+        }
+        self.renderer
+            .draw_segments(self.color, &[(previous, (x, y))]);
+        self.renderer.flush();
+    }
+
+    //When the trail has to be faded next, None while nothing fades
+    pub fn fade_deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    //The trail stays as it is, until it is cleared
+    pub fn stop_fading(&mut self) {
+        self.deadline = None;
+    }
+
+    //Redraws the trail with older points more transparent and forgets points, that have faded out
+    pub fn fade(&mut self) {
+        let now = Instant::now();
+        let fade = self.fade;
+        let alpha = |time: &Instant| {
+            let age = now.duration_since(*time).as_secs_f32() / fade.as_secs_f32();
+            ((1.0 - age) * FADE_LEVELS).ceil().max(0.0) / FADE_LEVELS
+        };
+        //The last point stays as the start of the next segment
+        let last = self.strokes.len().saturating_sub(1);
+        for (index, stroke) in self.strokes.iter_mut().enumerate() {
+            let keep = if index == last { 1 } else { 0 };
+            let faded = stroke
+                .iter()
+                .take_while(|(_, _, time)| alpha(time) == 0.0)
+                .count();
+            stroke.drain(..faded.min(stroke.len().saturating_sub(keep)));
+        }
+        self.strokes.retain(|stroke| !stroke.is_empty());
+        let mut layers: Vec<(u32, Vec<TrailSegment>)> = Vec::new();
+        for stroke in &self.strokes {
+            let mut previous = None;
+            for (x, y, time) in stroke {
+                let alpha = alpha(time);
+                if alpha > 0.0 {
+                    let color = fade_color(self.color, alpha);
+                    match layers.iter_mut().find(|(c, _)| *c == color) {
+                        Some((_, colored)) => colored.push((previous, (*x, *y))),
+                        None => layers.push((color, vec![(previous, (*x, *y))])),
+                    }
+                }
+                previous = Some((*x, *y));
+            }
+        }
+        self.renderer.redraw(&layers);
+        self.deadline = if layers.is_empty() {
+            None
+        } else {
+            Some(now + FADE_INTERVAL)
+        };
+        self.renderer.flush();
+    }
+
+    pub fn clear(&mut self) {
+        self.strokes.clear();
+        self.deadline = None;
+        self.renderer.clear();
+        self.renderer.flush();
+    }
+}
+
+//Scales all channels of the premultiplied pixel, which makes it more transparent
+fn fade_color(pixel: u32, factor: f32) -> u32 {
+    (0..4).fold(0, |faded, channel| {
+        let value = (pixel >> (channel * 8)) & 0xff;
+        faded | (((value as f32 * factor).round() as u32) << (channel * 8))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Remembers what would have been drawn
+    #[derive(Default)]
+    struct Recording {
+        segments: Vec<(u32, TrailSegment)>,
+        redraws: Vec<Vec<(u32, Vec<TrailSegment>)>>,
+        clears: usize,
+    }
+
+    impl Renderer for Recording {
+        fn draw_segments(&mut self, color: u32, segments: &[TrailSegment]) {
+            self.segments
+                .extend(segments.iter().map(|segment| (color, *segment)));
+        }
+
+        fn redraw(&mut self, layers: &[(u32, Vec<TrailSegment>)]) {
+            self.redraws.push(layers.to_vec());
+        }
+
+        fn clear(&mut self) {
+            self.clears += 1;
+        }
+
+        fn flush(&mut self) {}
+    }
+
+    #[test]
+    fn trail() {
+        let mut trail = Trail::new(Recording::default(), 0xff00ff00, Duration::ZERO);
+        trail.begin_stroke();
+        trail.add_point(1, 2);
+        trail.add_point(3, 4);
+        trail.begin_stroke();
+        trail.add_point(5, 6);
+        assert_eq!(
+            trail.renderer.segments,
+            vec![
+                (0xff00ff00, (None, (1, 2))),
+                (0xff00ff00, (Some((1, 2)), (3, 4))),
+                (0xff00ff00, (None, (5, 6))),
+            ]
+        );
+        assert!(trail.fade_deadline().is_none());
+        trail.clear();
+        assert_eq!(trail.renderer.clears, 1);
+    }
+
+    #[test]
+    fn fading() {
+        let mut trail = Trail::new(Recording::default(), 0xffffffff, Duration::from_secs(60));
+        trail.add_point(1, 2);
+        assert!(trail.fade_deadline().is_some());
+        trail.add_point(3, 4);
+        assert!(trail.renderer.segments.is_empty());
+        assert_eq!(
+            trail.renderer.redraws[1],
+            vec![(0xffffffff, vec![(None, (1, 2)), (Some((1, 2)), (3, 4))])]
+        );
+        trail.fade();
+        assert_eq!(trail.renderer.redraws.len(), 3);
+        trail.stop_fading();
+        assert!(trail.fade_deadline().is_none());
+        assert_eq!(fade_color(0x80402010, 0.5), 0x40201008);
+    }
+}
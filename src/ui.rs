@@ -1,41 +1,188 @@
+use super::backend::{self, Event, InputBackend, Listener};
+use super::trail::{Renderer, Trail, TrailSegment};
 use super::*;
+use super::{randr, render, xtest};
 
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 use xcb::ffi::*;
 use xcb::*;
 
+//Pointer movement in pixels, up to which a press of the trigger button is replayed as a click
+const CLICK_DISTANCE: i16 = 4;
+//Distance in pixels of the feedback text from the end of the stroke
+const FEEDBACK_OFFSET: i32 = 16;
+//Line width of the gesture shape drawn as feedback
+const SHAPE_WIDTH: u32 = 2;
+
 pub struct UI {
     window: u32,
-    conn: Connection,
+    conn: Rc<Connection>,
     gc: u32,
-    fg: u32,
     bg: u32,
-    radius: u32,
     screen_num: i32,
+    //Size of the root window, its monitors and how areas are resolved
+    screen: traps::Screen,
+    //RANDR 1.5 is available, otherwise the screen is one monitor
+    has_randr: bool,
+    overlay_monitor: config::OverlayMonitor,
+    //Position and size of the overlay
+    origin: (i16, i16),
     width: u16,
     height: u16,
     visible: bool,
+    stroke_pause: Duration,
+    //The window focused before the overlay was shown, reported with the next stroke
+    focused_window: Option<traps::WindowInfo>,
+    //The input focus before the overlay was shown, restored when it is hidden
+    previous_focus: Option<u32>,
+    //Grabbed button and modifiers, strokes are then recorded without the overlay
+    trigger: Option<(u8, u16)>,
+    //Watched besides the X connection, e.g. the control socket
+    watched_fds: Vec<RawFd>,
+    //Cleared to leave the event loop
+    running: bool,
+    trail: Trail<X11Renderer>,
+    feedback_time: Duration,
+    feedback_font: String,
+    feedback_color: u32,
+    feedback_shape: bool,
+    //Opened core font of the feedback text
+    font: Option<u32>,
+    //Set while the feedback of the last stroke sequence is shown, the overlay is hidden afterwards
+    feedback_deadline: Option<Instant>,
+    //The last drawn point, near which the feedback text is shown
+    last_point: (i16, i16),
+    key_bindings: Vec<config::KeyBinding>,
+    //Keycode and modifiers of the key bindings in the current keyboard mapping
+    keycodes: Vec<(u8, u16, config::KeyAction)>,
 }
 
 impl UI {
-    pub fn new(config: &config::Config) -> UI {
-        let (conn, screen_num) = xcb::Connection::connect(None).unwrap();
-        UI {
-            fg: config.fg,
+    pub fn new(config: &config::Config) -> Result<UI, String> {
+        let (conn, screen_num) = xcb::Connection::connect(None)
+            .map_err(|_| "could not connect to the X display".to_owned())?;
+        let conn = Rc::new(conn);
+        let gc = conn.generate_id();
+        let window = conn.generate_id();
+        let renderer = X11Renderer {
+            conn: conn.clone(),
+            window,
+            gc,
             bg: config.bg,
             radius: config.r,
-            gc: conn.generate_id(),
-            window: conn.generate_id(),
+            size: (0, 0),
+            root_size: (0, 0),
+            visual: 0,
+            antialias: config.trail_antialias,
+            render: None,
+            back_buffer: None,
+        };
+        Ok(UI {
+            bg: config.bg,
+            gc,
+            window,
             conn,
             screen_num,
+            screen: traps::Screen {
+                monitor_areas: config.monitor_areas,
+                ..Default::default()
+            },
+            has_randr: false,
+            overlay_monitor: config.overlay_monitor,
+            origin: (0, 0),
             visible: false,
             width: 0,
             height: 0,
+            stroke_pause: Duration::from_millis(config.stroke_pause),
+            focused_window: None,
+            previous_focus: None,
+            trigger: config.trigger,
+            watched_fds: Vec::new(),
+            running: false,
+            trail: Trail::new(
+                renderer,
+                config.fg,
+                Duration::from_millis(config.trail_fade),
+            ),
+            feedback_time: Duration::from_millis(config.feedback_time),
+            feedback_font: config.feedback_font.clone(),
+            feedback_color: config.feedback_color,
+            feedback_shape: config.feedback_shape,
+            font: None,
+            feedback_deadline: None,
+            last_point: (0, 0),
+            key_bindings: config.key_bindings.clone(),
+            keycodes: Vec::new(),
+        })
+    }
+
+    fn root(&self) -> u32 {
+        let setup = self.conn.get_setup();
+        setup.roots().nth(self.screen_num as usize).unwrap().root()
+    }
+
+    //Grabs the trigger button on the root window, also while Caps Lock or Num Lock are active
+    fn grab_trigger(&self, grab: bool) {
+        let (button, modifiers) = match self.trigger {
+            Some(trigger) => trigger,
+            None => return,
+        };
+        let locks: &[u32] = if modifiers as u32 & xcb::MOD_MASK_ANY != 0 {
+            &[0]
+        } else {
+            &[
+                0,
+                xcb::MOD_MASK_LOCK,
+                xcb::MOD_MASK_2,
+                xcb::MOD_MASK_LOCK | xcb::MOD_MASK_2,
+            ]
+        };
+        for lock in locks {
+            let modifiers = modifiers | *lock as u16;
+            if grab {
+                xcb::grab_button(
+                    &self.conn,
+                    false,
+                    self.root(),
+                    (xcb::EVENT_MASK_BUTTON_PRESS
+                        | xcb::EVENT_MASK_BUTTON_RELEASE
+                        | xcb::EVENT_MASK_BUTTON_MOTION) as u16,
+                    xcb::GRAB_MODE_ASYNC as u8,
+                    xcb::GRAB_MODE_ASYNC as u8,
+                    xcb::NONE,
+                    xcb::NONE,
+                    button,
+                    modifiers,
+                );
+            } else {
+                xcb::ungrab_button(&self.conn, button, self.root(), modifiers);
+            }
         }
+        self.conn.flush();
     }
 
-    pub fn init(&mut self) {
-        self.create_window();
-        self.make_gc();
+    //Sends a press of the trigger button, that did not become a stroke, on to the window below
+    fn replay_click(&mut self) {
+        let (button, _) = match self.trigger {
+            Some(trigger) => trigger,
+            None => return,
+        };
+        self.grab_trigger(false);
+        if !self.click(button) {
+            eprintln!("Could not replay the click, is XTEST available?");
+        }
+        //The fake click has to be processed, before the button is grabbed again
+        let _ = xcb::get_input_focus(&self.conn).get_reply();
+        self.grab_trigger(true);
+    }
+
+    fn input_focus(&self) -> u32 {
+        xcb::get_input_focus(&self.conn)
+            .get_reply()
+            .map(|reply| reply.focus())
+            .unwrap_or(xcb::NONE)
     }
 
     fn window_values(&self, colormap: u32) -> [(u32, u32); 5] {
@@ -58,11 +205,10 @@ impl UI {
     fn create_window(&mut self) {
         let setup = self.conn.get_setup();
         let screen = setup.roots().nth(self.screen_num as usize).unwrap();
-        self.width = screen.width_in_pixels();
-        self.height = screen.height_in_pixels();
         let colormap = self.conn.generate_id();
         unsafe {
             let visual = get_visual(screen.ptr).expect("Your screen does not support argb");
+            self.trail.renderer.visual = visual.as_ref().unwrap().visual_id;
             create_colormap(
                 &self.conn,
                 XCB_COLORMAP_ALLOC_NONE as u8,
@@ -106,62 +252,328 @@ impl UI {
             self.window,
             &[
                 (xcb::GC_FUNCTION, xcb::xproto::GX_COPY),
-                (xcb::GC_FOREGROUND, self.fg),
+                (xcb::GC_FOREGROUND, self.trail.color),
                 (xcb::GC_BACKGROUND, self.bg),
-                (xcb::GC_LINE_WIDTH, self.radius * 2),
+                (xcb::GC_LINE_WIDTH, self.trail.renderer.radius * 2),
+                (xcb::GC_CAP_STYLE, xcb::CAP_STYLE_ROUND),
+                (xcb::GC_JOIN_STYLE, xcb::JOIN_STYLE_ROUND),
                 (xcb::GC_GRAPHICS_EXPOSURES, 1),
             ],
         );
     }
 
-    fn draw_point(&self, x: i16, y: i16){
-        xcb::poly_fill_arc(
-            &self.conn,
-            self.window,
-            self.gc,
-            &[xcb::Arc::new(
-                x - self.radius as i16,
-                y - self.radius as i16,
-                self.radius as u16 * 2,
-                self.radius as u16 * 2,
-                0,
-                360 << 6,
-            )],
-        );
-        //xcb::poly_line(&self.conn, 0, self.window, self.gc, &[xcb::Point::new(oldx, oldy), xcb::Point::new(motion.event_x(), motion.event_y())]);
-        self.conn.flush();
+    //Falls back to the fixed font, which every X server has
+    fn open_font(&mut self) {
+        if let Some(font) = self.font.take() {
+            xcb::close_font(&self.conn, font);
+        }
+        for name in [self.feedback_font.as_str(), "fixed"] {
+            let font = self.conn.generate_id();
+            if xcb::open_font_checked(&self.conn, font, name)
+                .request_check()
+                .is_ok()
+            {
+                self.font = Some(font);
+                xcb::change_gc(&self.conn, self.gc, &[(xcb::GC_FONT, font)]);
+                return;
+            }
+            eprintln!("Could not open the font {}", name);
+        }
     }
 
-    fn clear(&self) {
-        xcb::change_gc(&self.conn, self.gc, &[(xcb::GC_FOREGROUND, self.bg)]);
-        xcb::poly_fill_rectangle(
+    //Looks up the keycodes of the bound keysyms, again whenever the keyboard mapping changes
+    //Keysyms on the shifted level of their key are bound with shift
+    fn map_key_bindings(&mut self) {
+        self.keycodes.clear();
+        for binding in &self.key_bindings {
+            match xtest::keycode(&self.conn, binding.keysym) {
+                Some((keycode, shifted)) => {
+                    let shift = if shifted {
+                        xcb::MOD_MASK_SHIFT as u16
+                    } else {
+                        0
+                    };
+                    self.keycodes.push((
+                        keycode,
+                        binding.modifiers | shift,
+                        binding.action.clone(),
+                    ));
+                }
+                None => eprintln!("No key produces the keysym {:#x}", binding.keysym),
+            }
+        }
+    }
+
+    //Caps Lock and Num Lock are ignored
+    fn key_binding(&self, keycode: u8, state: u16) -> Option<config::KeyAction> {
+        let modifiers = state
+            & (xcb::MOD_MASK_SHIFT | xcb::MOD_MASK_CONTROL | xcb::MOD_MASK_1 | xcb::MOD_MASK_4)
+                as u16;
+        self.keycodes
+            .iter()
+            .find(|(bound, bound_modifiers, _)| *bound == keycode && *bound_modifiers == modifiers)
+            .map(|(_, _, action)| action.clone())
+    }
+
+    //Walks up from the focused window to the first window with a WM_CLASS, the client window
+    fn client_window(&self, focus: u32) -> traps::WindowInfo {
+        let root = self.root();
+        let mut window = focus;
+        while window != xcb::NONE && window != xcb::INPUT_FOCUS_POINTER_ROOT && window != root {
+            if let Some(info) = window_info(&self.conn, window) {
+                return info;
+            }
+            window = match xcb::query_tree(&self.conn, window).get_reply() {
+                Ok(reply) => reply.parent(),
+                Err(_) => xcb::NONE,
+            };
+        }
+        traps::WindowInfo::default()
+    }
+
+    //Monitors can change at any time, so they are queried before every stroke sequence
+    fn update_monitors(&mut self) {
+        if self.has_randr {
+            self.screen.monitors = randr::monitors(&self.conn, self.root());
+        }
+    }
+
+    //Moves the overlay onto the monitor under the pointer or over the whole screen
+    fn place_overlay(&mut self) {
+        let monitor = match self.overlay_monitor {
+            config::OverlayMonitor::All => {
+                traps::Monitor::new("", 0, 0, self.screen.width, self.screen.height)
+            }
+            config::OverlayMonitor::Pointer => {
+                let pointer = xcb::query_pointer(&self.conn, self.root())
+                    .get_reply()
+                    .map(|reply| traps::Point::new(reply.root_x(), reply.root_y()))
+                    .unwrap_or_else(|_| traps::Point::new(0, 0));
+                self.screen.monitor_at(&pointer)
+            }
+        };
+        let (width, height) = (monitor.width as u16, monitor.height as u16);
+        if (monitor.x, monitor.y) == self.origin && (width, height) == (self.width, self.height) {
+            return;
+        }
+        xcb::configure_window(
             &self.conn,
             self.window,
-            self.gc,
-            &[xcb::Rectangle::new(0, 0, self.width, self.height)],
+            &[
+                (xcb::CONFIG_WINDOW_X as u16, monitor.x as i32 as u32),
+                (xcb::CONFIG_WINDOW_Y as u16, monitor.y as i32 as u32),
+                (xcb::CONFIG_WINDOW_WIDTH as u16, width as u32),
+                (xcb::CONFIG_WINDOW_HEIGHT as u16, height as u32),
+            ],
         );
-        xcb::change_gc(&self.conn, self.gc, &[(xcb::GC_FOREGROUND, self.fg)]);
-        self.conn.flush();
+        self.origin = (monitor.x, monitor.y);
+        self.width = width;
+        self.height = height;
+        self.trail.renderer.size = (width, height);
     }
 
-    pub fn set_visible(&mut self, visible: bool) {
-        if visible {
-            xcb::map_window(&self.conn, self.window);
-            xcb::set_input_focus(&self.conn, XCB_INPUT_FOCUS_PARENT as u8, self.window, 0);
-        } else {
-            xcb::unmap_window(&self.conn, self.window);
+    fn restore_focus(&mut self) {
+        if let Some(focus) = self.previous_focus.take() {
+            xcb::set_input_focus(&self.conn, XCB_INPUT_FOCUS_PARENT as u8, focus, 0);
         }
-        self.conn.flush();
-        self.visible = visible;
     }
 
-    pub fn is_visible(&self) -> bool {
-        self.visible
+    //Below and right of the last point, but kept on the screen
+    //Core fonts are drawn with 8 bit strings, of which at most 255 bytes fit into a request
+    fn draw_text(&self, text: &str) {
+        let font = match self.font {
+            Some(font) => font,
+            None => return,
+        };
+        let text: String = text
+            .chars()
+            .map(|c| {
+                if c.is_ascii() && !c.is_ascii_control() {
+                    c
+                } else {
+                    '?'
+                }
+            })
+            .take(255)
+            .collect();
+        let chars: Vec<xcb::Char2b> = text.bytes().map(|byte| xcb::Char2b::new(0, byte)).collect();
+        let (width, ascent, descent) = xcb::query_text_extents(&self.conn, font, &chars)
+            .get_reply()
+            .map(|reply| {
+                (
+                    reply.overall_width(),
+                    reply.font_ascent() as i32,
+                    reply.font_descent() as i32,
+                )
+            })
+            .unwrap_or((0, 0, 0));
+        let (x, y) = self.last_point;
+        let x = (x as i32 + FEEDBACK_OFFSET)
+            .min(self.width as i32 - width)
+            .max(0);
+        let y = (y as i32 + FEEDBACK_OFFSET + ascent)
+            .min(self.height as i32 - descent)
+            .max(ascent);
+        xcb::image_text_8(&self.conn, self.window, self.gc, x as i16, y as i16, &text);
     }
 
-    pub fn event_loop(&mut self, listener: Box<dyn Fn(Event)>) {
+    //Hides the overlay until the next show, while the listener handles the end of the stroke sequence
+    //With feedback, the overlay stays until the feedback time is over, but gives the focus back
+    fn finish(&mut self, listener: &mut Listener) {
+        if self.is_passive() {
+            listener(Event::Stop, self);
+            listener(Event::Finish, self);
+            return;
+        }
+        if !self.feedback_time.is_zero() {
+            //The trail stays as it is, until the feedback is over
+            self.trail.stop_fading();
+            self.restore_focus();
+            self.feedback_deadline = Some(Instant::now() + self.feedback_time);
+            listener(Event::Stop, self);
+            listener(Event::Finish, self);
+            return;
+        }
+        self.set_visible(false);
+        listener(Event::Stop, self);
+        listener(Event::Finish, self);
+        self.trail.clear();
+    }
+
+    //Waits for the next event, or for the end of the stroke pause, after which the sequence is finished
+    //Meanwhile readable watched file descriptors are reported to the listener
+    fn next_event(
+        &mut self,
+        pause_deadline: &mut Option<Instant>,
+        listener: &mut Listener,
+    ) -> Option<xcb::GenericEvent> {
+        loop {
+            if !self.running {
+                return None;
+            }
+            if let Some(event) = self.conn.poll_for_event() {
+                return Some(event);
+            }
+            if self.conn.has_error().is_err() {
+                return None;
+            }
+            let now = Instant::now();
+            if self
+                .feedback_deadline
+                .is_some_and(|deadline| now >= deadline)
+            {
+                self.set_visible(false);
+                continue;
+            }
+            if pause_deadline.is_some_and(|deadline| now >= deadline) {
+                *pause_deadline = None;
+                self.finish(listener);
+                continue;
+            }
+            if self
+                .trail
+                .fade_deadline()
+                .is_some_and(|deadline| now >= deadline)
+            {
+                self.trail.fade();
+                continue;
+            }
+            let deadline = [
+                *pause_deadline,
+                self.feedback_deadline,
+                self.trail.fade_deadline(),
+            ]
+            .iter()
+            .flatten()
+            .min()
+            .copied();
+            let connection = self.conn.as_raw_fd();
+            let fds: Vec<RawFd> = std::iter::once(connection)
+                .chain(self.watched_fds.iter().copied())
+                .collect();
+            for fd in backend::wait(&fds, deadline) {
+                if fd != connection {
+                    listener(Event::Readable(fd), self);
+                }
+            }
+        }
+    }
+
+    //Records the strokes drawn with the grabbed trigger button
+    //A press without movement is replayed as a click
+    fn passive_event_loop(&mut self, listener: &mut Listener) {
+        let (button, _) = self.trigger.unwrap();
+        let mut pause_deadline: Option<Instant> = None;
+        //Position and time of the trigger press
+        let mut press: Option<(i16, i16, u32)> = None;
+        let mut is_stroke = false;
+        while let Some(event) = self.next_event(&mut pause_deadline, listener) {
+            match event.response_type() & !0x80 {
+                xcb::BUTTON_PRESS => {
+                    let button_press: &xcb::ButtonPressEvent = unsafe { xcb::cast_event(&event) };
+                    if button_press.detail() == button {
+                        press = Some((
+                            button_press.root_x(),
+                            button_press.root_y(),
+                            button_press.time(),
+                        ));
+                        is_stroke = false;
+                    }
+                }
+                xcb::MOTION_NOTIFY => {
+                    let motion: &xcb::MotionNotifyEvent = unsafe { xcb::cast_event(&event) };
+                    let (x, y, time) = match press {
+                        Some(press) => press,
+                        None => continue,
+                    };
+                    if !is_stroke {
+                        let distance = (motion.root_x() - x).abs().max((motion.root_y() - y).abs());
+                        if distance < CLICK_DISTANCE {
+                            continue;
+                        }
+                        is_stroke = true;
+                        if pause_deadline.take().is_some() {
+                            listener(Event::Stop, self);
+                        } else {
+                            self.update_monitors();
+                            let window = self.client_window(self.input_focus());
+                            listener(Event::Focus(window), self);
+                        }
+                        listener(Event::Start, self);
+                        listener(Event::Point(x, y, time), self);
+                    }
+                    listener(
+                        Event::Point(motion.root_x(), motion.root_y(), motion.time()),
+                        self,
+                    );
+                }
+                xcb::BUTTON_RELEASE => {
+                    let button_release: &xcb::ButtonReleaseEvent =
+                        unsafe { xcb::cast_event(&event) };
+                    if button_release.detail() != button || press.take().is_none() {
+                        continue;
+                    }
+                    if !is_stroke {
+                        if pause_deadline.take().is_some() {
+                            self.finish(listener);
+                        }
+                        self.replay_click();
+                    } else if self.stroke_pause.as_millis() == 0 {
+                        self.finish(listener);
+                    } else {
+                        pause_deadline = Some(Instant::now() + self.stroke_pause);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn overlay_event_loop(&mut self, listener: &mut Listener) {
+        //Set after a button release, while the next stroke of the sequence may still begin
+        let mut pause_deadline: Option<Instant> = None;
         loop {
-            let event = self.conn.wait_for_event();
+            let event = self.next_event(&mut pause_deadline, listener);
             match event {
                 None => {
                     break;
@@ -172,28 +584,50 @@ impl UI {
                         xcb::EXPOSE => {}
                         xcb::KEY_PRESS => {
                             let key_press: &xcb::KeyPressEvent = unsafe { xcb::cast_event(&event) };
-                            if key_press.detail() == 9 {
-                                break;
-                            } else if key_press.detail() == 23 {
-                                self.set_visible(false);
-                            } else {
-                                println!("{}", key_press.detail());
+                            match self.key_binding(key_press.detail(), key_press.state()) {
+                                Some(config::KeyAction::Cancel) => {
+                                    pause_deadline = None;
+                                    self.set_visible(false);
+                                    self.trail.clear();
+                                    listener(Event::Cancel, self);
+                                }
+                                Some(action) => listener(Event::Key(action), self),
+                                None => {}
                             }
                         }
+                        xcb::MAPPING_NOTIFY => {
+                            self.map_key_bindings();
+                        }
                         xcb::BUTTON_PRESS => {
-                            listener(Event::Start);
+                            //A new stroke sequence ends the feedback of the last one
+                            if self.feedback_deadline.is_some() {
+                                self.set_visible(true);
+                            }
+                            if pause_deadline.take().is_some() {
+                                listener(Event::Stop, self);
+                            }
+                            if let Some(window) = self.focused_window.take() {
+                                listener(Event::Focus(window), self);
+                            }
+                            self.trail.begin_stroke();
+                            listener(Event::Start, self);
                         }
                         xcb::BUTTON_RELEASE => {
-                            self.set_visible(false);
-                            listener(Event::Stop);
-                            self.clear();
-                            self.set_visible(true);
+                            if self.stroke_pause.as_millis() == 0 {
+                                self.finish(listener);
+                            } else {
+                                pause_deadline = Some(Instant::now() + self.stroke_pause);
+                            }
                         }
                         xcb::MOTION_NOTIFY => {
                             let motion: &xcb::MotionNotifyEvent =
                                 unsafe { xcb::cast_event(&event) };
-                            self.draw_point(motion.event_x(), motion.event_y());
-                            listener(Event::Point(motion.event_x(), motion.event_y()));
+                            self.trail.add_point(motion.event_x(), motion.event_y());
+                            self.last_point = (motion.event_x(), motion.event_y());
+                            listener(
+                                Event::Point(motion.root_x(), motion.root_y(), motion.time()),
+                                self,
+                            );
                         }
                         _ => {}
                     }
@@ -203,6 +637,483 @@ impl UI {
     }
 }
 
+impl InputBackend for UI {
+    fn init(&mut self) {
+        let setup = self.conn.get_setup();
+        let screen = setup.roots().nth(self.screen_num as usize).unwrap();
+        self.width = screen.width_in_pixels();
+        self.height = screen.height_in_pixels();
+        self.screen.width = self.width as i16;
+        self.screen.height = self.height as i16;
+        self.trail.renderer.size = (self.width, self.height);
+        self.trail.renderer.root_size = (self.width, self.height);
+        self.has_randr = randr::is_supported(&self.conn);
+        self.update_monitors();
+        if self.trigger.is_some() {
+            self.grab_trigger(true);
+        } else {
+            self.create_window();
+            self.make_gc();
+            self.open_font();
+            self.trail
+                .renderer
+                .set_antialias(self.trail.renderer.antialias);
+            self.map_key_bindings();
+        }
+    }
+
+    //Reports Event::Readable, whenever the file descriptor becomes readable
+    fn watch(&mut self, fd: RawFd) {
+        self.watched_fds.push(fd);
+    }
+
+    //Takes over the colors, radius, stroke pause, trail and feedback of a reloaded config
+    //The trigger button is only grabbed at the start
+    fn apply_config(&mut self, config: &config::Config) {
+        self.bg = config.bg;
+        self.trail.color = config.fg;
+        self.trail.fade = Duration::from_millis(config.trail_fade);
+        self.trail.renderer.bg = config.bg;
+        self.trail.renderer.radius = config.r;
+        self.stroke_pause = Duration::from_millis(config.stroke_pause);
+        self.overlay_monitor = config.overlay_monitor;
+        self.screen.monitor_areas = config.monitor_areas;
+        self.feedback_time = Duration::from_millis(config.feedback_time);
+        self.feedback_color = config.feedback_color;
+        self.feedback_shape = config.feedback_shape;
+        let font_changed = config.feedback_font != self.feedback_font;
+        self.feedback_font = config.feedback_font.clone();
+        self.key_bindings = config.key_bindings.clone();
+        if config.trigger != self.trigger {
+            eprintln!("The trigger button changes after a restart");
+        }
+        if !self.is_passive() {
+            xcb::change_window_attributes(
+                &self.conn,
+                self.window,
+                &[(xcb::CW_BACK_PIXEL, self.bg), (xcb::CW_BORDER_PIXEL, self.bg)],
+            );
+            xcb::change_gc(&self.conn, self.gc, &[(xcb::GC_BACKGROUND, self.bg)]);
+            if font_changed {
+                self.open_font();
+            }
+            if config.trail_antialias != self.trail.renderer.antialias {
+                self.trail.renderer.set_antialias(config.trail_antialias);
+            }
+            self.map_key_bindings();
+            self.conn.flush();
+        }
+    }
+
+    fn event_loop(&mut self, mut listener: Listener) {
+        self.running = true;
+        if self.is_passive() {
+            self.passive_event_loop(&mut listener);
+        } else {
+            self.overlay_event_loop(&mut listener);
+        }
+    }
+
+    //The event loop returns, once the listener is done with the current event
+    fn stop_event_loop(&mut self) {
+        self.running = false;
+    }
+
+    //The root window with the monitors at the last stroke sequence
+    fn screen(&self) -> traps::Screen {
+        self.screen.clone()
+    }
+
+    fn is_passive(&self) -> bool {
+        self.trigger.is_some()
+    }
+
+    fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    //Without an overlay in the passive mode, nothing is shown
+    //Ends the feedback of the last stroke sequence
+    fn set_visible(&mut self, visible: bool) {
+        if self.is_passive() {
+            return;
+        }
+        let feedback = self.feedback_deadline.take().is_some();
+        if feedback {
+            self.trail.clear();
+        }
+        //While shown, the overlay has the focus, so the focus is only recorded when the overlay appears
+        //or takes the focus back from the window, to which it was returned for the feedback
+        if visible == self.visible && !feedback {
+            return;
+        }
+        if visible {
+            self.update_monitors();
+            self.place_overlay();
+            let focus = self.input_focus();
+            self.previous_focus = Some(focus);
+            self.focused_window = Some(self.client_window(focus));
+            xcb::map_window(&self.conn, self.window);
+            xcb::set_input_focus(&self.conn, XCB_INPUT_FOCUS_PARENT as u8, self.window, 0);
+        } else {
+            xcb::unmap_window(&self.conn, self.window);
+            self.restore_focus();
+        }
+        self.conn.flush();
+        self.visible = visible;
+    }
+
+    //Shows the text near the end of the stroke sequence and the shape of the matched gesture in root coordinates
+    //Only while the overlay shows feedback, i.e. between the end of the sequence and the feedback time
+    fn show_feedback(&self, text: &str, shape: &[Vec<traps::Point>]) {
+        if self.feedback_deadline.is_none() {
+            return;
+        }
+        xcb::change_gc(
+            &self.conn,
+            self.gc,
+            &[
+                (xcb::GC_FOREGROUND, self.feedback_color),
+                (xcb::GC_LINE_WIDTH, SHAPE_WIDTH),
+            ],
+        );
+        if self.feedback_shape {
+            for stroke in shape {
+                let points: Vec<xcb::Point> = stroke
+                    .iter()
+                    .map(|point| xcb::Point::new(point.x - self.origin.0, point.y - self.origin.1))
+                    .collect();
+                xcb::poly_line(
+                    &self.conn,
+                    xcb::COORD_MODE_ORIGIN as u8,
+                    self.window,
+                    self.gc,
+                    &points,
+                );
+            }
+        }
+        self.draw_text(text);
+        self.conn.flush();
+    }
+
+    //Presses the keys in order and releases them in reverse order through XTEST
+    //Keysyms on the shifted level of their key are typed with shift
+    //The overlay is hidden first, so the keys reach the window that was focused before it
+    //This also ends the feedback, since the mapped overlay would get the input instead
+    fn send_key_chord(&mut self, keysyms: &[u32]) -> bool {
+        if !xtest::is_available(&self.conn) {
+            return false;
+        }
+        if self.visible {
+            self.set_visible(false);
+        }
+        let mut keycodes = Vec::new();
+        for keysym in keysyms {
+            match xtest::keycode(&self.conn, *keysym) {
+                Some((keycode, shifted)) => {
+                    if shifted && !keysyms.contains(&traps::keysym::SHIFT) {
+                        match xtest::keycode(&self.conn, traps::keysym::SHIFT) {
+                            Some((shift, _)) => keycodes.push(shift),
+                            None => return false,
+                        }
+                    }
+                    keycodes.push(keycode);
+                }
+                None => return false,
+            }
+        }
+        for keycode in &keycodes {
+            xtest::fake_input(&self.conn, xtest::KEY_PRESS, *keycode);
+        }
+        for keycode in keycodes.iter().rev() {
+            xtest::fake_input(&self.conn, xtest::KEY_RELEASE, *keycode);
+        }
+        self.conn.flush();
+        true
+    }
+
+    //Clicks at the current pointer position through XTEST
+    //The overlay is hidden first, so the click reaches the window below it, even while it shows feedback
+    fn click(&mut self, button: u8) -> bool {
+        if !xtest::is_available(&self.conn) {
+            return false;
+        }
+        if self.visible {
+            self.set_visible(false);
+        }
+        xtest::fake_input(&self.conn, xtest::BUTTON_PRESS, button);
+        xtest::fake_input(&self.conn, xtest::BUTTON_RELEASE, button);
+        self.conn.flush();
+        true
+    }
+
+    fn machine(&self) -> config::Machine {
+        config::Machine {
+            resolution: Some((self.screen.width as u16, self.screen.height as u16)),
+            ..config::Machine::current()
+        }
+    }
+
+    fn window_inspector(&self) -> Option<Box<dyn backend::WindowInspector>> {
+        X11WindowInspector::new()
+            .map(|inspector| Box::new(inspector) as Box<dyn backend::WindowInspector>)
+    }
+}
+
+//Draws the trail on the overlay window, anti-aliased through RENDER if enabled
+pub struct X11Renderer {
+    conn: Rc<Connection>,
+    window: u32,
+    gc: u32,
+    bg: u32,
+    radius: u32,
+    //Size of the overlay
+    size: (u16, u16),
+    //Size of the root window, which the back buffer covers
+    root_size: (u16, u16),
+    //ARGB visual of the overlay
+    visual: u32,
+    antialias: bool,
+    //Set while the trail is anti-aliased
+    render: Option<render::Render>,
+    //A fading trail is redrawn here and then copied to the overlay, so the overlay does not flicker
+    back_buffer: Option<u32>,
+}
+
+impl X11Renderer {
+    //Falls back to the core protocol without RENDER
+    fn set_antialias(&mut self, enabled: bool) {
+        self.antialias = enabled;
+        if let Some(mut render) = self.render.take() {
+            render.free(&self.conn);
+        }
+        if enabled {
+            self.render = render::Render::new(&self.conn, self.visual, self.window, self.root_size);
+            if self.render.is_none() {
+                eprintln!("RENDER is not available, the trail is drawn without anti-aliasing");
+            }
+        }
+    }
+
+    fn triangles(&self, segments: &[TrailSegment]) -> Vec<render::Triangle> {
+        let radius = self.radius as f32;
+        let mut triangles = Vec::new();
+        for (from, (x, y)) in segments {
+            let to = (*x as f32, *y as f32);
+            triangles.extend(render::circle(to, radius));
+            if let Some((x, y)) = from {
+                triangles.extend(render::rectangle((*x as f32, *y as f32), to, radius));
+            }
+        }
+        triangles
+    }
+
+    //Draws with the core protocol, which replaces the pixels instead of blending them
+    fn draw(&self, drawable: u32, color: u32, segments: &[TrailSegment]) {
+        let lines: Vec<xcb::Segment> = segments
+            .iter()
+            .filter_map(|(from, (x, y))| from.map(|(x1, y1)| xcb::Segment::new(x1, y1, *x, *y)))
+            .collect();
+        let dots: Vec<xcb::Arc> = segments
+            .iter()
+            .filter(|(from, _)| from.is_none())
+            .map(|(_, (x, y))| {
+                xcb::Arc::new(
+                    x - self.radius as i16,
+                    y - self.radius as i16,
+                    self.radius as u16 * 2,
+                    self.radius as u16 * 2,
+                    0,
+                    360 << 6,
+                )
+            })
+            .collect();
+        xcb::change_gc(
+            &self.conn,
+            self.gc,
+            &[
+                (xcb::GC_FOREGROUND, color),
+                (xcb::GC_LINE_WIDTH, self.radius * 2),
+            ],
+        );
+        xcb::poly_segment(&self.conn, drawable, self.gc, &lines);
+        xcb::poly_fill_arc(&self.conn, drawable, self.gc, &dots);
+    }
+
+    fn fill_background(&self, drawable: u32, area: render::Area) {
+        let (x, y, width, height) = area;
+        xcb::change_gc(&self.conn, self.gc, &[(xcb::GC_FOREGROUND, self.bg)]);
+        xcb::poly_fill_rectangle(
+            &self.conn,
+            drawable,
+            self.gc,
+            &[xcb::Rectangle::new(x, y, width, height)],
+        );
+    }
+
+    fn back_buffer(&mut self) -> u32 {
+        match self.back_buffer {
+            Some(back_buffer) => back_buffer,
+            None => {
+                let back_buffer = self.conn.generate_id();
+                xcb::create_pixmap(
+                    &self.conn,
+                    32,
+                    back_buffer,
+                    self.window,
+                    self.root_size.0,
+                    self.root_size.1,
+                );
+                self.back_buffer = Some(back_buffer);
+                back_buffer
+            }
+        }
+    }
+
+    //Shows the area of the back buffer on the overlay
+    fn copy_to_window(&self, back_buffer: u32, area: render::Area) {
+        let (x, y, width, height) = area;
+        xcb::copy_area(
+            &self.conn,
+            back_buffer,
+            self.window,
+            self.gc,
+            x,
+            y,
+            x,
+            y,
+            width,
+            height,
+        );
+    }
+}
+
+impl Renderer for X11Renderer {
+    //With RENDER, the segments are added to the coverage of the trail,
+    //which is composited again where they are drawn
+    fn draw_segments(&mut self, color: u32, segments: &[TrailSegment]) {
+        let triangles = match self.render {
+            Some(_) => self.triangles(segments),
+            None => return self.draw(self.window, color, segments),
+        };
+        let area = match render::bounds(&triangles, self.size) {
+            Some(area) => area,
+            None => return,
+        };
+        let back_buffer = self.back_buffer();
+        self.fill_background(back_buffer, area);
+        if let Some(render) = &mut self.render {
+            render.add_coverage(&self.conn, &triangles);
+            render.composite(&self.conn, back_buffer, color, area);
+        }
+        self.copy_to_window(back_buffer, area);
+    }
+
+    //Each color is composited once, through the coverage of its segments
+    fn redraw(&mut self, layers: &[(u32, Vec<TrailSegment>)]) {
+        let back_buffer = self.back_buffer();
+        let area = (0, 0, self.size.0, self.size.1);
+        self.fill_background(back_buffer, area);
+        for (color, segments) in layers {
+            let triangles = match self.render {
+                Some(_) => self.triangles(segments),
+                None => {
+                    self.draw(back_buffer, *color, segments);
+                    continue;
+                }
+            };
+            if let Some(render) = &mut self.render {
+                render.clear_coverage(&self.conn);
+                render.add_coverage(&self.conn, &triangles);
+                render.composite(&self.conn, back_buffer, *color, area);
+            }
+        }
+        self.copy_to_window(back_buffer, area);
+    }
+
+    fn clear(&mut self) {
+        self.fill_background(self.window, (0, 0, self.size.0, self.size.1));
+        if let Some(render) = &mut self.render {
+            render.clear_coverage(&self.conn);
+        }
+    }
+
+    fn flush(&mut self) {
+        self.conn.flush();
+    }
+}
+
+//Looks up windows on its own connection, so it can be used while the UI runs its event loop
+pub struct X11WindowInspector {
+    conn: Connection,
+    root: u32,
+}
+
+impl X11WindowInspector {
+    pub fn new() -> Option<X11WindowInspector> {
+        let (conn, screen_num) = xcb::Connection::connect(None).ok()?;
+        let root = conn.get_setup().roots().nth(screen_num as usize)?.root();
+        Some(X11WindowInspector { conn, root })
+    }
+}
+
+impl backend::WindowInspector for X11WindowInspector {
+    //Descends through the windows stacked at the root coordinates until a client window is found
+    fn window_at(&self, x: i16, y: i16) -> traps::WindowInfo {
+        let mut window = self.root;
+        loop {
+            let child =
+                match xcb::translate_coordinates(&self.conn, self.root, window, x, y).get_reply() {
+                    Ok(reply) => reply.child(),
+                    Err(_) => xcb::NONE,
+                };
+            if child == xcb::NONE {
+                return traps::WindowInfo::default();
+            }
+            if let Some(info) = window_info(&self.conn, child) {
+                return info;
+            }
+            window = child;
+        }
+    }
+}
+
+fn intern_atom(conn: &Connection, name: &str) -> u32 {
+    xcb::intern_atom(conn, false, name)
+        .get_reply()
+        .map(|reply| reply.atom())
+        .unwrap_or(xcb::NONE)
+}
+
+fn get_text_property(conn: &Connection, window: u32, property: u32) -> Option<Vec<u8>> {
+    let reply = xcb::get_property(conn, false, window, property, xcb::ATOM_ANY, 0, 1024)
+        .get_reply()
+        .ok()?;
+    let value: &[u8] = reply.value();
+    if value.is_empty() {
+        None
+    } else {
+        Some(value.to_vec())
+    }
+}
+
+//Only windows with a WM_CLASS are considered client windows
+fn window_info(conn: &Connection, window: u32) -> Option<traps::WindowInfo> {
+    let class = get_text_property(conn, window, xcb::ATOM_WM_CLASS)?;
+    let name = get_text_property(conn, window, intern_atom(conn, "_NET_WM_NAME"))
+        .or_else(|| get_text_property(conn, window, xcb::ATOM_WM_NAME))
+        .unwrap_or_default();
+    Some(traps::WindowInfo {
+        id: window,
+        class: class
+            .split(|byte| *byte == 0)
+            .filter(|part| !part.is_empty())
+            .map(|part| String::from_utf8_lossy(part).into_owned())
+            .collect(),
+        name: String::from_utf8_lossy(&name).into_owned(),
+    })
+}
+
 unsafe fn get_visual(
     screen: *mut xcb::ffi::xcb_screen_t,
 ) -> Option<*mut xcb::ffi::xcb_visualtype_t> {
@@ -214,13 +1125,16 @@ unsafe fn get_visual(
     None
 }
 
-pub fn color_to_argb(r: u32, g: u32, b: u32, a: u32) -> u32 {
-    (a << 24) | ((r << 16) * a / 255) | ((g << 8) * a / 255) | (b * a / 255)
-}
-
-#[derive(Debug)]
-pub enum Event {
-    Point(i16, i16),
-    Start,
-    Stop
+//The current machine with the resolution of the default screen, to select config sections
+pub fn machine() -> config::Machine {
+    let resolution = Connection::connect(None)
+        .ok()
+        .and_then(|(conn, screen_num)| {
+            let screen = conn.get_setup().roots().nth(screen_num as usize)?;
+            Some((screen.width_in_pixels(), screen.height_in_pixels()))
+        });
+    config::Machine {
+        resolution,
+        ..config::Machine::current()
+    }
 }
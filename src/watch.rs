@@ -0,0 +1,135 @@
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::PathBuf;
+
+//Watches the directories of the config files through inotify, since editors often replace files instead of writing to them
+pub struct ConfigWatch {
+    fd: RawFd,
+    //Watch descriptor of the directory and the pattern of the file name
+    files: Vec<(libc::c_int, glob::Pattern)>,
+}
+
+impl ConfigWatch {
+    pub fn new(files: &[PathBuf]) -> Option<ConfigWatch> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return None;
+        }
+        let mut watch = ConfigWatch {
+            fd,
+            files: Vec::new(),
+        };
+        watch.set_files(files);
+        Some(watch)
+    }
+
+    //Files can be glob patterns, whose directories do not contain globs
+    //Directories, which do not exist, are not watched
+    pub fn set_files(&mut self, files: &[PathBuf]) {
+        let mut watched = Vec::new();
+        for file in files {
+            let name = file.file_name().and_then(|name| name.to_str());
+            let pattern = match name.and_then(|name| glob::Pattern::new(name).ok()) {
+                Some(pattern) => pattern,
+                None => continue,
+            };
+            let dir = match file
+                .parent()
+                .and_then(|dir| CString::new(dir.as_os_str().as_bytes()).ok())
+            {
+                Some(dir) => dir,
+                None => continue,
+            };
+            let mask = libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO;
+            let wd = unsafe { libc::inotify_add_watch(self.fd, dir.as_ptr(), mask) };
+            if wd >= 0 {
+                watched.push((wd, pattern));
+            }
+        }
+        for (wd, _) in &self.files {
+            if !watched.iter().any(|(watched_wd, _)| watched_wd == wd) {
+                unsafe {
+                    libc::inotify_rm_watch(self.fd, *wd);
+                }
+            }
+        }
+        self.files = watched;
+    }
+
+    //Reads all pending events and returns whether one of them concerns a config file
+    pub fn changed(&self) -> bool {
+        let mut changed = false;
+        let mut buffer = [0u8; 4096];
+        loop {
+            let length = unsafe {
+                libc::read(
+                    self.fd,
+                    buffer.as_mut_ptr() as *mut libc::c_void,
+                    buffer.len(),
+                )
+            };
+            if length <= 0 {
+                return changed;
+            }
+            let mut offset = 0;
+            let header = std::mem::size_of::<libc::inotify_event>();
+            while offset + header <= length as usize {
+                let event: libc::inotify_event = unsafe {
+                    std::ptr::read_unaligned(buffer[offset..].as_ptr() as *const libc::inotify_event)
+                };
+                let name = &buffer[offset + header..offset + header + event.len as usize];
+                let name = name.split(|byte| *byte == 0).next().unwrap_or_default();
+                let name = String::from_utf8_lossy(name);
+                changed |= self
+                    .files
+                    .iter()
+                    .any(|(wd, pattern)| *wd == event.wd && pattern.matches(&name));
+                offset += header + event.len as usize;
+            }
+        }
+    }
+}
+
+impl AsRawFd for ConfigWatch {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl Drop for ConfigWatch {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_changed() {
+        let dir = std::env::temp_dir().join(format!("traps-watch-{}", std::process::id()));
+        let fragments = dir.join("conf.d");
+        std::fs::create_dir_all(&fragments).unwrap();
+        let mut watch = ConfigWatch::new(&[dir.join("trapsrc")]).unwrap();
+        assert!(!watch.changed());
+        std::fs::write(dir.join("other"), "r = 5").unwrap();
+        assert!(!watch.changed());
+        std::fs::write(dir.join("trapsrc.new"), "r = 5").unwrap();
+        std::fs::rename(dir.join("trapsrc.new"), dir.join("trapsrc")).unwrap();
+        assert!(watch.changed());
+        assert!(!watch.changed());
+        //Included files and new fragments
+        watch.set_files(&[dir.join("other"), fragments.join("*.conf")]);
+        std::fs::write(dir.join("other"), "r = 6").unwrap();
+        assert!(watch.changed());
+        std::fs::write(fragments.join("keys.conf"), "r = 7").unwrap();
+        assert!(watch.changed());
+        std::fs::write(dir.join("trapsrc"), "r = 8").unwrap();
+        assert!(!watch.changed());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
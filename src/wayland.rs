@@ -0,0 +1,76 @@
+use super::backend::{self, Event, InputBackend, Listener};
+use std::os::unix::io::RawFd;
+use traps::config;
+
+//Stands in for a Wayland backend, which would need a compositor protocol for global pointer input
+//Without input, gestures can only be triggered through the control socket
+pub struct WaylandBackend {
+    watched_fds: Vec<RawFd>,
+    running: bool,
+}
+
+impl WaylandBackend {
+    pub fn new(_config: &config::Config) -> WaylandBackend {
+        WaylandBackend {
+            watched_fds: Vec::new(),
+            running: false,
+        }
+    }
+}
+
+impl InputBackend for WaylandBackend {
+    fn init(&mut self) {
+        eprintln!("The Wayland backend has no input yet, gestures can only be triggered by name");
+    }
+
+    fn watch(&mut self, fd: RawFd) {
+        self.watched_fds.push(fd);
+    }
+
+    fn apply_config(&mut self, _config: &config::Config) {}
+
+    fn event_loop(&mut self, mut listener: Listener) {
+        self.running = true;
+        while self.running {
+            for fd in backend::wait(&self.watched_fds, None) {
+                listener(Event::Readable(fd), self);
+            }
+        }
+    }
+
+    fn stop_event_loop(&mut self) {
+        self.running = false;
+    }
+
+    fn screen(&self) -> traps::Screen {
+        traps::Screen::default()
+    }
+
+    fn is_passive(&self) -> bool {
+        true
+    }
+
+    fn is_visible(&self) -> bool {
+        false
+    }
+
+    fn set_visible(&mut self, _visible: bool) {}
+
+    fn show_feedback(&self, _text: &str, _shape: &[Vec<traps::Point>]) {}
+
+    fn send_key_chord(&mut self, _keysyms: &[u32]) -> bool {
+        false
+    }
+
+    fn click(&mut self, _button: u8) -> bool {
+        false
+    }
+
+    fn machine(&self) -> config::Machine {
+        config::Machine::current()
+    }
+
+    fn window_inspector(&self) -> Option<Box<dyn backend::WindowInspector>> {
+        None
+    }
+}
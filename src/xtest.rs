@@ -0,0 +1,40 @@
+use xcb::Connection;
+
+pub const KEY_PRESS: u8 = 2;
+pub const KEY_RELEASE: u8 = 3;
+pub const BUTTON_PRESS: u8 = 4;
+pub const BUTTON_RELEASE: u8 = 5;
+
+//Requests of a missing extension would close the connection
+pub fn is_available(conn: &Connection) -> bool {
+    conn.get_extension_data(xcb::test::id())
+        .map(|data| data.present())
+        .unwrap_or(false)
+}
+
+//Fakes a key or button event at the current time
+pub fn fake_input(conn: &Connection, kind: u8, detail: u8) {
+    xcb::test::fake_input(conn, kind, detail, xcb::CURRENT_TIME, xcb::NONE, 0, 0, 0);
+}
+
+//Finds a keycode producing the keysym and whether shift is needed for it
+pub fn keycode(conn: &Connection, keysym: u32) -> Option<(u8, bool)> {
+    let setup = conn.get_setup();
+    let min = setup.min_keycode();
+    let count = setup.max_keycode() - min + 1;
+    let reply = xcb::get_keyboard_mapping(conn, min, count)
+        .get_reply()
+        .ok()?;
+    let per_keycode = reply.keysyms_per_keycode() as usize;
+    if per_keycode == 0 {
+        return None;
+    }
+    let keysyms = reply.keysyms();
+    //Unshifted keysyms are preferred
+    (0..per_keycode.min(2)).find_map(|column| {
+        keysyms
+            .chunks(per_keycode)
+            .position(|columns| columns[column] == keysym)
+            .map(|offset| (min + offset as u8, column == 1))
+    })
+}